@@ -0,0 +1,10 @@
+-- Your SQL goes here
+create table if not exists sessions (
+    id UUID primary key default uuid_generate_v4(),
+    user_id UUID not null references users (id) on delete cascade,
+    token_hash varchar unique not null,
+    created_at timestamp not null default now(),
+    expires_at timestamp not null
+);
+
+create index sessions_user_id_idx on sessions (user_id);
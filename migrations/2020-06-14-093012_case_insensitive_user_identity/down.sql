@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+drop index if exists users_username_key;
+drop index if exists users_email_key;
+
+alter table users add constraint users_username_key unique (username);
+alter table users add constraint users_email_key unique (email);
@@ -0,0 +1,39 @@
+-- Your SQL goes here
+
+-- Accounts that differ only by case would make the unique indexes below fail
+-- halfway through, so stop early and name them; merge or rename them by hand
+-- and rerun.
+do $$
+declare
+    conflicts text;
+begin
+    select string_agg(field || ' [' || ids || ']', '; ')
+    into conflicts
+    from (
+        select 'username' as field,
+               string_agg(id::text, ', ' order by id) as ids
+        from users
+        group by lower(username)
+        having count(*) > 1
+        union all
+        select 'email', string_agg(id::text, ', ' order by id)
+        from users
+        group by lower(email)
+        having count(*) > 1
+    ) duplicates;
+
+    if conflicts is not null then
+        raise exception 'users differing only by case must be resolved '
+            'before this migration: %', conflicts;
+    end if;
+end $$;
+
+update users set email = lower(email);
+
+alter table users drop constraint if exists users_username_key;
+alter table users drop constraint if exists users_email_key;
+
+-- index names match the old constraints so unique violations keep
+-- reporting `users_username_key` / `users_email_key`
+create unique index users_username_key on users (lower(username));
+create unique index users_email_key on users (lower(email));
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+create table if not exists posts (
+    id UUID primary key default uuid_generate_v4(),
+    author_id UUID not null references users (id) on delete cascade,
+    body text not null,
+    created_at timestamp not null default now(),
+    updated_at timestamp not null default now()
+);
+
+create index posts_author_id_created_at_idx on posts (author_id, created_at desc, id desc);
+
+select diesel_manage_updated_at('posts');
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+create table if not exists follows (
+    follower_id UUID not null references users (id) on delete cascade,
+    followee_id UUID not null references users (id) on delete cascade,
+    created_at timestamp not null default now(),
+    primary key (follower_id, followee_id),
+    constraint follows_no_self_follow check (follower_id <> followee_id)
+);
+
+create index follows_follower_id_created_at_idx on follows (follower_id, created_at desc);
+create index follows_followee_id_created_at_idx on follows (followee_id, created_at desc);
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+create table if not exists timeline_entries (
+    user_id UUID not null references users (id) on delete cascade,
+    post_id UUID not null references posts (id) on delete cascade,
+    author_id UUID not null references users (id) on delete cascade,
+    created_at timestamp not null,
+    primary key (user_id, post_id)
+);
+
+create index timeline_entries_user_id_created_at_idx on timeline_entries (user_id, created_at desc, post_id desc);
+create index timeline_entries_author_id_idx on timeline_entries (author_id);
+
+-- Backfill every timeline from the existing posts and follows
+insert into timeline_entries (user_id, post_id, author_id, created_at)
+select posts.author_id, posts.id, posts.author_id, posts.created_at
+from posts
+union all
+select follows.follower_id, posts.id, posts.author_id, posts.created_at
+from follows
+join posts on posts.author_id = follows.followee_id
+on conflict do nothing;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+create type reaction_kind as enum ('like', 'love', 'laugh');
+
+create table if not exists reactions (
+    post_id UUID not null references posts (id) on delete cascade,
+    user_id UUID not null references users (id) on delete cascade,
+    kind reaction_kind not null,
+    created_at timestamp not null default now(),
+    primary key (post_id, user_id)
+);
+
+create index reactions_post_id_created_at_idx on reactions (post_id, created_at desc, user_id desc);
+create index reactions_user_id_idx on reactions (user_id);
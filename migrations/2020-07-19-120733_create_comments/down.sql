@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+drop trigger if exists delete_user_comments on users;
+drop trigger if exists delete_post_comments on posts;
+drop function if exists delete_subject_comments();
+drop table if exists comments;
+drop type if exists comment_subject;
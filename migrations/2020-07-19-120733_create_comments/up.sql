@@ -0,0 +1,34 @@
+-- Your SQL goes here
+create type comment_subject as enum ('post', 'user');
+
+create table if not exists comments (
+    id UUID primary key default uuid_generate_v4(),
+    subject_type comment_subject not null,
+    subject_id UUID not null,
+    parent_comment_id UUID references comments (id) on delete cascade,
+    author_id UUID not null references users (id) on delete cascade,
+    body text not null,
+    created_at timestamp not null default now(),
+    updated_at timestamp not null default now(),
+    deleted_at timestamp
+);
+
+create index comments_subject_created_at_idx on comments (subject_type, subject_id, created_at, id) where parent_comment_id is null;
+create index comments_parent_comment_id_idx on comments (parent_comment_id);
+
+select diesel_manage_updated_at('comments');
+
+-- subject_id can't carry a foreign key, so threads are removed alongside
+-- the row they hang off.
+create or replace function delete_subject_comments() returns trigger as $$
+begin
+    delete from comments
+    where subject_type = TG_ARGV[0]::comment_subject and subject_id = OLD.id;
+    return OLD;
+end;
+$$ language plpgsql;
+
+create trigger delete_post_comments after delete on posts
+    for each row execute procedure delete_subject_comments('post');
+create trigger delete_user_comments after delete on users
+    for each row execute procedure delete_subject_comments('user');
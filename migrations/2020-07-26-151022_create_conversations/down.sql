@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop table if exists messages;
+drop table if exists conversation_members;
+drop table if exists conversations;
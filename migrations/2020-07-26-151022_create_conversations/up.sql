@@ -0,0 +1,30 @@
+-- Your SQL goes here
+create table if not exists conversations (
+    id UUID primary key default uuid_generate_v4(),
+    created_at timestamp not null default now(),
+    updated_at timestamp not null default now(),
+    last_message_at timestamp not null default now()
+);
+
+create index conversations_last_message_at_idx on conversations (last_message_at desc, id desc);
+
+select diesel_manage_updated_at('conversations');
+
+create table if not exists conversation_members (
+    conversation_id UUID not null references conversations (id) on delete cascade,
+    user_id UUID not null references users (id) on delete cascade,
+    joined_at timestamp not null default now(),
+    primary key (conversation_id, user_id)
+);
+
+create index conversation_members_user_id_idx on conversation_members (user_id);
+
+create table if not exists messages (
+    id UUID primary key default uuid_generate_v4(),
+    conversation_id UUID not null references conversations (id) on delete cascade,
+    sender_id UUID not null references users (id) on delete cascade,
+    body text not null,
+    created_at timestamp not null default now()
+);
+
+create index messages_conversation_id_created_at_idx on messages (conversation_id, created_at desc, id desc);
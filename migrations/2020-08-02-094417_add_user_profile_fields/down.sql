@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+drop trigger if exists set_updated_at on users;
+
+alter table users
+    drop column if exists display_name,
+    drop column if exists bio,
+    drop column if exists avatar_url,
+    drop column if exists location,
+    drop column if exists website,
+    drop column if exists created_at,
+    drop column if exists updated_at;
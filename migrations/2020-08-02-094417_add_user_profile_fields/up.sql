@@ -0,0 +1,11 @@
+-- Your SQL goes here
+alter table users
+    add column display_name varchar,
+    add column bio text,
+    add column avatar_url varchar,
+    add column location varchar,
+    add column website varchar,
+    add column created_at timestamp not null default now(),
+    add column updated_at timestamp not null default now();
+
+select diesel_manage_updated_at('users');
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop index if exists users_deleted_at_idx;
+alter table users drop column if exists deleted_at;
+alter table users drop column if exists deactivated_at;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table users add column deactivated_at timestamp;
+alter table users add column deleted_at timestamp;
+
+create index users_deleted_at_idx on users (deleted_at)
+    where deleted_at is not null;
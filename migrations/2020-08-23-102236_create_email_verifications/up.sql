@@ -0,0 +1,13 @@
+-- Your SQL goes here
+alter table users add column email_verified_at timestamp;
+
+create table if not exists email_verifications (
+    id UUID primary key default uuid_generate_v4(),
+    user_id UUID not null references users (id) on delete cascade,
+    email varchar not null,
+    token_hash varchar unique not null,
+    created_at timestamp not null default now(),
+    expires_at timestamp not null
+);
+
+create index email_verifications_user_id_idx on email_verifications (user_id);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+alter table comments
+    drop constraint comments_parent_comment_id_fkey,
+    add constraint comments_parent_comment_id_fkey
+        foreign key (parent_comment_id) references comments (id) on delete cascade;
+
+delete from comments where author_id is null;
+alter table comments
+    drop constraint comments_author_id_fkey,
+    add constraint comments_author_id_fkey
+        foreign key (author_id) references users (id) on delete cascade;
+alter table comments alter column author_id set not null;
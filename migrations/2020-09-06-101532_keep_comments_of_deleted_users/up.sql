@@ -0,0 +1,17 @@
+-- Your SQL goes here
+
+-- Purging an account keeps its comments in place, authorless, so replies
+-- from other users don't vanish with it.
+alter table comments alter column author_id drop not null;
+alter table comments
+    drop constraint comments_author_id_fkey,
+    add constraint comments_author_id_fkey
+        foreign key (author_id) references users (id) on delete set null;
+
+-- Comments are only ever soft-deleted on their own; a hard delete of a
+-- comment that still has replies is refused. Whole threads still go in one
+-- statement when their subject is deleted, which `no action` allows.
+alter table comments
+    drop constraint comments_parent_comment_id_fkey,
+    add constraint comments_parent_comment_id_fkey
+        foreign key (parent_comment_id) references comments (id) on delete no action;
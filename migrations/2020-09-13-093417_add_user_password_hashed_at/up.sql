@@ -0,0 +1,9 @@
+-- Your SQL goes here
+
+-- Marks rows whose password column holds a hash. Rows left null still hold
+-- plaintext from before hashing was introduced, whatever that text looks
+-- like.
+alter table users add column password_hashed_at timestamp;
+
+update users set password_hashed_at = updated_at
+where password ~ '^\$argon2(id|i|d)\$v=[0-9]+\$m=[0-9]+,t=[0-9]+,p=[0-9]+\$[A-Za-z0-9+/]+\$[A-Za-z0-9+/]+$';
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+drop index if exists users_created_at_id_idx;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+create index users_created_at_id_idx on users (created_at, id);
@@ -0,0 +1,381 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{delete, get, path, post, Filter, Rejection};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::error::{ApiError, FieldError};
+use crate::filters::json_body;
+use crate::session::handler::{with_auth, with_verified_auth};
+use crate::user::model::User;
+use crate::ConnectionPool;
+
+use super::model::{clamp_depth, ListParams, NewComment, Subject, SubjectType};
+use super::repository::CommentRepo;
+use super::{validation, view};
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let comment_create_route = subject()
+        .and(post())
+        .and(with_verified_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(json_body())
+        .and_then(comment_create);
+
+    let comment_index_route = subject()
+        .and(get())
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool.clone()))
+        .and_then(comment_index);
+
+    let comment_details_route = path!("comments" / Uuid)
+        .and(get())
+        .and(warp::query::<ThreadQuery>())
+        .and(with_db_conn(pool.clone()))
+        .and_then(comment_details);
+
+    let comment_delete_route = path!("comments" / Uuid)
+        .and(delete())
+        .and(with_auth(pool.clone(), settings))
+        .and(with_db_conn(pool))
+        .and_then(comment_delete);
+
+    comment_create_route
+        .or(comment_index_route)
+        .or(comment_details_route)
+        .or(comment_delete_route)
+}
+
+/// Matches `/posts/{id}/comments` and `/users/{id}/comments`.
+fn subject() -> impl Filter<Extract = (Subject,), Error = Rejection> + Clone {
+    let post_subject = path!("posts" / Uuid / "comments").map(|id| Subject {
+        kind: SubjectType::Post,
+        id,
+    });
+    let user_subject = path!("users" / Uuid / "comments").map(|id| Subject {
+        kind: SubjectType::User,
+        id,
+    });
+    post_subject.or(user_subject).unify()
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RequestBody {
+    pub body: String,
+    pub parent_comment_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<Uuid>,
+    pub depth: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct ThreadQuery {
+    pub depth: Option<i64>,
+}
+
+async fn comment_create(
+    subject: Subject,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: RequestBody,
+) -> Result<WithStatus<Json>, Infallible> {
+    let req = match validation::validate_new_comment(req) {
+        Ok(req) => req,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let result = blocking(move || {
+        CommentRepo::subject_owner(&conn, subject)?;
+        if let Some(parent_id) = req.parent_comment_id {
+            check_parent(&conn, subject, parent_id)?;
+        }
+        let new_comment = NewComment {
+            subject_type: subject.kind,
+            subject_id: subject.id,
+            parent_comment_id: req.parent_comment_id,
+            author_id: current_user.id,
+            body: req.body,
+        };
+        Ok::<_, ApiError>(CommentRepo::create(&conn, new_comment)?)
+    })
+    .await;
+
+    match result {
+        Ok(comment) => {
+            let resp = view::comment_create(&comment);
+            Ok(with_status(json(&resp), StatusCode::CREATED))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+/// Replies must stay on the parent's subject and can't revive a deleted
+/// comment's thread.
+fn check_parent(
+    conn: &PgConnection,
+    subject: Subject,
+    parent_id: Uuid,
+) -> Result<(), ApiError> {
+    let invalid = |message: &str| {
+        ApiError::Validation(vec![FieldError::new(
+            "parent_comment_id",
+            message,
+        )])
+    };
+    match CommentRepo::find(conn, parent_id) {
+        Ok(parent) if parent.subject() != subject => {
+            Err(invalid("must be a comment on the same subject"))
+        }
+        Ok(parent) if parent.is_deleted() => {
+            Err(invalid("must not be a deleted comment"))
+        }
+        Ok(_) => Ok(()),
+        Err(diesel::result::Error::NotFound) => {
+            Err(invalid("must be an existing comment"))
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn comment_index(
+    subject: Subject,
+    query: IndexQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        CommentRepo::subject_owner(&conn, subject)?;
+        CommentRepo::list(&conn, subject, &params)
+    })
+    .await;
+
+    match result {
+        Ok((threads, next_cursor)) => {
+            let resp = view::thread_page(&threads, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn comment_details(
+    id: Uuid,
+    query: ThreadQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let depth = clamp_depth(query.depth);
+    match blocking(move || CommentRepo::thread(&conn, id, depth)).await {
+        Ok(thread) => {
+            let resp = view::thread_details(&thread);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+/// Either the comment's author or the owner of what it's posted on may
+/// delete it.
+async fn comment_delete(
+    id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        let comment = CommentRepo::find(&conn, id)?;
+        if comment.author_id != Some(current_user.id)
+            && CommentRepo::subject_owner(&conn, comment.subject())?
+                != current_user.id
+        {
+            return Err(ApiError::Forbidden);
+        }
+        if !comment.is_deleted() {
+            CommentRepo::soft_delete(&conn, id)?;
+        }
+        Ok(())
+    })
+    .await;
+
+    match result {
+        Ok(()) => Ok(with_status(
+            json(&json!({ "success": true })),
+            StatusCode::OK,
+        )),
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use warp::Reply;
+
+    use crate::error::handle_rejection;
+    use crate::post::model::Post;
+    use crate::test_helpers::{
+        create_post, create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    fn post_subject(post: &Post) -> Subject {
+        Subject {
+            kind: SubjectType::Post,
+            id: post.id,
+        }
+    }
+
+    fn request(body: &str, parent_comment_id: Option<Uuid>) -> RequestBody {
+        RequestBody {
+            body: body.to_string(),
+            parent_comment_id,
+        }
+    }
+
+    async fn body_json(resp: WithStatus<Json>) -> (StatusCode, Value) {
+        let (parts, body) = resp.into_response().into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (parts.status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn create(
+        pool: &ConnectionPool,
+        subject: Subject,
+        author: &User,
+        req: RequestBody,
+    ) -> (StatusCode, Value) {
+        let resp =
+            comment_create(subject, author.clone(), pool.get().unwrap(), req)
+                .await
+                .unwrap();
+        body_json(resp).await
+    }
+
+    #[tokio::test]
+    async fn comment_create_threads_replies_under_parent() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        drop(conn);
+
+        let (status, root) =
+            create(&pool, post_subject(&post), &bob, request(" hi ", None))
+                .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let root_id: Uuid = serde_json::from_value(root["id"].clone()).unwrap();
+
+        let req = request("reply", Some(root_id));
+        let (status, _) = create(&pool, post_subject(&post), &bob, req).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let resp = comment_index(
+            post_subject(&post),
+            IndexQuery::default(),
+            pool.get().unwrap(),
+        )
+        .await
+        .unwrap();
+        let (status, body) = body_json(resp).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["body"], "hi");
+        assert_eq!(body["data"][0]["replies"][0]["body"], "reply");
+    }
+
+    #[tokio::test]
+    async fn comment_create_rejects_parent_on_another_subject() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        drop(conn);
+
+        let profile = Subject {
+            kind: SubjectType::User,
+            id: bob.id,
+        };
+        let (_, root) = create(&pool, profile, &bob, request("hi", None)).await;
+        let root_id: Uuid = serde_json::from_value(root["id"].clone()).unwrap();
+
+        let req = request("reply", Some(root_id));
+        let (status, body) =
+            create(&pool, post_subject(&post), &bob, req).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["field"], "parent_comment_id");
+    }
+
+    #[tokio::test]
+    async fn comment_create_returns_404_for_unknown_subject() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+        let subject = Subject {
+            kind: SubjectType::Post,
+            id: Uuid::new_v4(),
+        };
+
+        let (status, _) =
+            create(&pool, subject, &bob, request("hi", None)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn comment_delete_allows_author_and_subject_owner_only() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        drop(conn);
+
+        let (_, comment) =
+            create(&pool, post_subject(&post), &alice, request("hi", None))
+                .await;
+        let id: Uuid = serde_json::from_value(comment["id"].clone()).unwrap();
+
+        let resp = comment_delete(id, eve, pool.get().unwrap()).await.unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::FORBIDDEN);
+
+        let resp = comment_delete(id, bob, pool.get().unwrap()).await.unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::OK);
+
+        let resp =
+            comment_details(id, ThreadQuery::default(), pool.get().unwrap())
+                .await
+                .unwrap();
+        let (_, body) = body_json(resp).await;
+        assert_eq!(body["body"], "[deleted]");
+    }
+
+    #[tokio::test]
+    async fn comment_routes_cover_user_profiles() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+        let filter = routes(pool, session_settings()).recover(handle_rejection);
+
+        let resp = warp::test::request()
+            .method("GET")
+            .path(&format!("/users/{}/comments?depth=2", bob.id))
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(body["data"], json!([]));
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::{Insertable, Queryable};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::schema::comments;
+use crate::user::model::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use super::handler::IndexQuery;
+
+pub const DEFAULT_DEPTH: i64 = 3;
+pub const MAX_DEPTH: i64 = 10;
+
+/// Diesel's handle on the `comment_subject` Postgres enum.
+#[derive(SqlType)]
+#[postgres(type_name = "comment_subject")]
+pub struct CommentSubjectType;
+
+/// The kind of content a comment thread hangs off.
+#[derive(
+    AsExpression, FromSqlRow, Serialize, PartialEq, Eq, Clone, Copy, Debug,
+)]
+#[sql_type = "CommentSubjectType"]
+#[serde(rename_all = "lowercase")]
+pub enum SubjectType {
+    Post,
+    User,
+}
+
+impl ToSql<CommentSubjectType, Pg> for SubjectType {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let label: &[u8] = match self {
+            SubjectType::Post => b"post",
+            SubjectType::User => b"user",
+        };
+        out.write_all(label)?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<CommentSubjectType, Pg> for SubjectType {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"post" => Ok(SubjectType::Post),
+            b"user" => Ok(SubjectType::User),
+            _ => Err("Unrecognized comment_subject variant".into()),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Subject {
+    pub kind: SubjectType,
+    pub id: Uuid,
+}
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct Comment {
+    pub id: Uuid,
+    pub subject_type: SubjectType,
+    pub subject_id: Uuid,
+    pub parent_comment_id: Option<Uuid>,
+    /// `None` once the author's account has been purged.
+    pub author_id: Option<Uuid>,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+impl Comment {
+    pub fn subject(&self) -> Subject {
+        Subject {
+            kind: self.subject_type,
+            id: self.subject_id,
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "comments"]
+pub struct NewComment {
+    pub subject_type: SubjectType,
+    pub subject_id: Uuid,
+    pub parent_comment_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub body: String,
+}
+
+/// A comment with its replies, cut off at the requested depth;
+/// `more_replies` flags where the cut hid further replies.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Thread {
+    pub comment: Comment,
+    pub replies: Vec<Thread>,
+    pub more_replies: bool,
+}
+
+/// Top-level comments run oldest first; `cursor` is the id of the last one
+/// already seen and `depth` counts levels including the top one.
+#[derive(PartialEq, Debug)]
+pub struct ListParams {
+    pub limit: i64,
+    pub cursor: Option<Uuid>,
+    pub depth: i64,
+}
+
+impl From<IndexQuery> for ListParams {
+    fn from(query: IndexQuery) -> Self {
+        ListParams {
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            cursor: query.cursor,
+            depth: clamp_depth(query.depth),
+        }
+    }
+}
+
+pub fn clamp_depth(depth: Option<i64>) -> i64 {
+    depth.unwrap_or(DEFAULT_DEPTH).clamp(1, MAX_DEPTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_params_clamp_depth() {
+        let params: ListParams = IndexQuery {
+            depth: Some(50),
+            ..Default::default()
+        }
+        .into();
+        assert_eq!(params.depth, MAX_DEPTH);
+
+        let params: ListParams = IndexQuery::default().into();
+        assert_eq!(params.depth, DEFAULT_DEPTH);
+    }
+}
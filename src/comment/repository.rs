@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet};
+
+use diesel::dsl::{now, EqAny, Filter, IsNull, Nullable, Or, Select};
+use diesel::prelude::*;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::into_page;
+use crate::post::repository::PostRepo;
+use crate::schema::{comments, users};
+use crate::user::repository::{active, Active, UserRepo};
+
+use super::model::{
+    Comment, ListParams, NewComment, Subject, SubjectType, Thread,
+};
+
+pub struct CommentRepo;
+
+impl CommentRepo {
+    pub fn create(
+        conn: &PgConnection,
+        new_comment: NewComment,
+    ) -> QueryResult<Comment> {
+        diesel::insert_into(comments::table)
+            .values(new_comment)
+            .get_result(conn)
+    }
+
+    pub fn find(conn: &PgConnection, comment_id: Uuid) -> QueryResult<Comment> {
+        comments::table.find(comment_id).first(conn)
+    }
+
+    /// The user entitled to moderate a subject's comments: the post author
+    /// or the profile's own user. Fails with `NotFound` for a missing subject.
+    pub fn subject_owner(
+        conn: &PgConnection,
+        subject: Subject,
+    ) -> QueryResult<Uuid> {
+        match subject.kind {
+            SubjectType::Post => {
+                Ok(PostRepo::find(conn, subject.id)?.author_id)
+            }
+            SubjectType::User => Ok(UserRepo::find(conn, subject.id)?.id),
+        }
+    }
+
+    /// Top-level comments on a subject, oldest first, each with replies
+    /// nested up to `params.depth` levels. The second value is the cursor
+    /// for the following page, if any.
+    pub fn list(
+        conn: &PgConnection,
+        subject: Subject,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<Thread>, Option<Uuid>)> {
+        let mut query = comments::table
+            .filter(comments::subject_type.eq(subject.kind))
+            .filter(comments::subject_id.eq(subject.id))
+            .filter(comments::parent_comment_id.is_null())
+            .filter(visible())
+            .order((comments::created_at.asc(), comments::id.asc()))
+            .into_boxed();
+
+        if let Some(cursor) = params.cursor {
+            let last = Self::find(conn, cursor)?;
+            query = query.filter(
+                comments::created_at.gt(last.created_at).or(
+                    comments::created_at
+                        .eq(last.created_at)
+                        .and(comments::id.gt(last.id)),
+                ),
+            );
+        }
+
+        let page = query.limit(params.limit + 1).load::<Comment>(conn)?;
+        let (page, next_cursor) =
+            into_page(page, params.limit, |comment| comment.id);
+
+        Ok((Self::threads(conn, page, params.depth)?, next_cursor))
+    }
+
+    /// A single comment with its replies nested up to `depth` levels.
+    pub fn thread(
+        conn: &PgConnection,
+        comment_id: Uuid,
+        depth: i64,
+    ) -> QueryResult<Thread> {
+        let root = Self::find(conn, comment_id)?;
+        let mut threads = Self::threads(conn, vec![root], depth)?;
+        Ok(threads.remove(0))
+    }
+
+    /// Marks the comment deleted and drops its body. The row stays so its
+    /// replies keep their place in the thread.
+    pub fn soft_delete(
+        conn: &PgConnection,
+        comment_id: Uuid,
+    ) -> QueryResult<Comment> {
+        diesel::update(comments::table.find(comment_id))
+            .set((
+                comments::body.eq(""),
+                comments::deleted_at.eq(now.nullable()),
+            ))
+            .get_result(conn)
+    }
+
+    /// Loads replies one level at a time, so a thread of depth `d` costs
+    /// `d` queries regardless of its width.
+    fn threads(
+        conn: &PgConnection,
+        roots: Vec<Comment>,
+        depth: i64,
+    ) -> QueryResult<Vec<Thread>> {
+        let mut children: HashMap<Uuid, Vec<Comment>> = HashMap::new();
+        let mut frontier: Vec<Uuid> = roots.iter().map(|c| c.id).collect();
+
+        for _ in 1..depth {
+            if frontier.is_empty() {
+                break;
+            }
+            let replies = Self::replies_to(conn, &frontier)?;
+            frontier = replies.iter().map(|c| c.id).collect();
+            for reply in replies {
+                if let Some(parent_id) = reply.parent_comment_id {
+                    children.entry(parent_id).or_default().push(reply);
+                }
+            }
+        }
+
+        let truncated: HashSet<Uuid> = if frontier.is_empty() {
+            HashSet::new()
+        } else {
+            comments::table
+                .filter(comments::parent_comment_id.eq_any(&frontier))
+                .filter(visible())
+                .select(comments::parent_comment_id)
+                .distinct()
+                .load::<Option<Uuid>>(conn)?
+                .into_iter()
+                .flatten()
+                .collect()
+        };
+
+        Ok(roots
+            .into_iter()
+            .map(|root| assemble(root, &mut children, &truncated))
+            .collect())
+    }
+
+    fn replies_to(
+        conn: &PgConnection,
+        parent_ids: &[Uuid],
+    ) -> QueryResult<Vec<Comment>> {
+        comments::table
+            .filter(comments::parent_comment_id.eq_any(parent_ids))
+            .filter(visible())
+            .order((comments::created_at.asc(), comments::id.asc()))
+            .load(conn)
+    }
+}
+
+type Visible = Or<
+    IsNull<comments::author_id>,
+    EqAny<
+        comments::author_id,
+        Select<Filter<users::table, Active>, Nullable<users::id>>,
+    >,
+>;
+
+/// Hides comments, with their replies, while the author's account is
+/// deactivated or awaiting deletion. Once the account is purged they come
+/// back masked, so the thread keeps its shape.
+fn visible() -> Visible {
+    comments::author_id.is_null().or(comments::author_id
+        .eq_any(users::table.filter(active()).select(users::id.nullable())))
+}
+
+fn assemble(
+    comment: Comment,
+    children: &mut HashMap<Uuid, Vec<Comment>>,
+    truncated: &HashSet<Uuid>,
+) -> Thread {
+    let replies = children
+        .remove(&comment.id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|reply| assemble(reply, children, truncated))
+        .collect();
+    let more_replies = truncated.contains(&comment.id);
+    Thread {
+        comment,
+        replies,
+        more_replies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::post::model::NewPost;
+    use crate::schema::users;
+    use crate::test_helpers::{create_user, establish_connection};
+    use crate::user::model::User;
+
+    use super::*;
+
+    fn comment(
+        conn: &PgConnection,
+        subject: Subject,
+        author: &User,
+        parent: Option<&Comment>,
+        body: &str,
+    ) -> Comment {
+        let new_comment = NewComment {
+            subject_type: subject.kind,
+            subject_id: subject.id,
+            parent_comment_id: parent.map(|p| p.id),
+            author_id: author.id,
+            body: body.to_string(),
+        };
+        CommentRepo::create(conn, new_comment)
+            .expect("Failed to create comment")
+    }
+
+    fn params(depth: i64) -> ListParams {
+        ListParams {
+            limit: 20,
+            cursor: None,
+            depth,
+        }
+    }
+
+    #[test]
+    fn list_nests_replies_up_to_depth() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let subject = Subject {
+            kind: SubjectType::User,
+            id: bob.id,
+        };
+        let root = comment(&conn, subject, &bob, None, "root");
+        let reply = comment(&conn, subject, &bob, Some(&root), "reply");
+        comment(&conn, subject, &bob, Some(&reply), "nested");
+
+        let (threads, next_cursor) =
+            CommentRepo::list(&conn, subject, &params(2)).unwrap();
+
+        assert_eq!(next_cursor, None);
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].comment, root);
+        assert!(!threads[0].more_replies);
+        assert_eq!(threads[0].replies[0].comment, reply);
+        assert!(threads[0].replies[0].replies.is_empty());
+        assert!(threads[0].replies[0].more_replies);
+
+        let thread = CommentRepo::thread(&conn, root.id, 3).unwrap();
+        assert_eq!(thread.replies[0].replies[0].comment.body, "nested");
+        assert!(!thread.replies[0].replies[0].more_replies);
+    }
+
+    #[test]
+    fn list_pages_top_level_comments_oldest_first() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = PostRepo::create(
+            &conn,
+            NewPost {
+                author_id: bob.id,
+                body: "hello".to_string(),
+            },
+        )
+        .unwrap();
+        let subject = Subject {
+            kind: SubjectType::Post,
+            id: post.id,
+        };
+        let first = comment(&conn, subject, &bob, None, "first");
+        let second = comment(&conn, subject, &bob, None, "second");
+
+        let page = ListParams {
+            limit: 1,
+            ..params(1)
+        };
+        let (first_page, next_cursor) =
+            CommentRepo::list(&conn, subject, &page).unwrap();
+        assert_eq!(next_cursor, Some(first_page[0].comment.id));
+
+        let page = ListParams {
+            cursor: next_cursor,
+            ..page
+        };
+        let (second_page, next_cursor) =
+            CommentRepo::list(&conn, subject, &page).unwrap();
+        assert_eq!(next_cursor, None);
+
+        // Rows created in one test transaction share a timestamp, so only
+        // the id tiebreak orders them.
+        let mut seen = vec![
+            first_page[0].comment.clone(),
+            second_page[0].comment.clone(),
+        ];
+        seen.sort_by_key(|c| c.id);
+        let mut expected = vec![first, second];
+        expected.sort_by_key(|c| c.id);
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn soft_delete_keeps_replies_attached() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let subject = Subject {
+            kind: SubjectType::User,
+            id: bob.id,
+        };
+        let root = comment(&conn, subject, &bob, None, "root");
+        comment(&conn, subject, &bob, Some(&root), "reply");
+
+        let deleted = CommentRepo::soft_delete(&conn, root.id).unwrap();
+        assert!(deleted.is_deleted());
+        assert_eq!(deleted.body, "");
+
+        let thread = CommentRepo::thread(&conn, root.id, 2).unwrap();
+        assert_eq!(thread.replies[0].comment.body, "reply");
+    }
+
+    #[test]
+    fn deleting_a_post_removes_its_comments() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = PostRepo::create(
+            &conn,
+            NewPost {
+                author_id: bob.id,
+                body: "hello".to_string(),
+            },
+        )
+        .unwrap();
+        let subject = Subject {
+            kind: SubjectType::Post,
+            id: post.id,
+        };
+        let root = comment(&conn, subject, &bob, None, "root");
+
+        PostRepo::delete(&conn, post.id).unwrap();
+
+        assert_eq!(
+            CommentRepo::find(&conn, root.id),
+            Err(diesel::result::Error::NotFound)
+        );
+    }
+
+    #[test]
+    fn purging_an_author_keeps_their_comments_and_replies() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let subject = Subject {
+            kind: SubjectType::User,
+            id: bob.id,
+        };
+        let root = comment(&conn, subject, &alice, None, "root");
+        comment(&conn, subject, &bob, Some(&root), "reply");
+
+        diesel::delete(users::table.find(alice.id))
+            .execute(&conn)
+            .unwrap();
+
+        let thread = CommentRepo::thread(&conn, root.id, 2).unwrap();
+        assert_eq!(thread.comment.author_id, None);
+        assert_eq!(thread.replies[0].comment.body, "reply");
+    }
+
+    #[test]
+    fn list_hides_comments_of_inactive_authors() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let eve = create_user(&conn);
+        let subject = Subject {
+            kind: SubjectType::User,
+            id: bob.id,
+        };
+        let kept = comment(&conn, subject, &bob, None, "kept");
+        comment(&conn, subject, &eve, Some(&kept), "hidden reply");
+        comment(&conn, subject, &eve, None, "hidden");
+        UserRepo::deactivate(&conn, eve.id).unwrap();
+
+        let (threads, _) =
+            CommentRepo::list(&conn, subject, &params(2)).unwrap();
+
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].comment, kept);
+        assert!(threads[0].replies.is_empty());
+    }
+
+    #[test]
+    fn hard_deleting_a_comment_with_replies_is_refused() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let subject = Subject {
+            kind: SubjectType::User,
+            id: bob.id,
+        };
+        let root = comment(&conn, subject, &bob, None, "root");
+        comment(&conn, subject, &bob, Some(&root), "reply");
+
+        let result =
+            diesel::delete(comments::table.find(root.id)).execute(&conn);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::error::{ApiError, FieldError};
+
+use super::handler::RequestBody;
+
+const BODY_MAX_LENGTH: usize = 2000;
+
+/// Trims the comment body and rejects it when empty or too long.
+pub fn validate_new_comment(req: RequestBody) -> Result<RequestBody, ApiError> {
+    let body = req.body.trim().to_string();
+    let length = body.chars().count();
+
+    if length == 0 {
+        Err(ApiError::Validation(vec![FieldError::new(
+            "body",
+            "must not be blank",
+        )]))
+    } else if length > BODY_MAX_LENGTH {
+        Err(ApiError::Validation(vec![FieldError::new(
+            "body",
+            format!("must be at most {} characters", BODY_MAX_LENGTH),
+        )]))
+    } else {
+        Ok(RequestBody { body, ..req })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: &str) -> RequestBody {
+        RequestBody {
+            body: body.to_string(),
+            parent_comment_id: None,
+        }
+    }
+
+    #[test]
+    fn validate_new_comment_trims_and_bounds_body() {
+        assert_eq!(validate_new_comment(request(" hi ")).unwrap().body, "hi");
+        assert!(validate_new_comment(request("\n")).is_err());
+        assert!(validate_new_comment(request(&"a".repeat(2001))).is_err());
+    }
+}
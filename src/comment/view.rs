@@ -0,0 +1,116 @@
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use super::model::{Comment, Thread};
+
+const DELETED_PLACEHOLDER: &str = "[deleted]";
+
+/// Deleted comments, and those left behind by purged accounts, keep their
+/// place in the thread but lose their body and author.
+pub fn comment_details(comment: &Comment) -> Value {
+    let deleted = comment.is_deleted() || comment.author_id.is_none();
+    let (author_id, body) = if deleted {
+        (None, DELETED_PLACEHOLDER)
+    } else {
+        (comment.author_id, comment.body.as_str())
+    };
+    json!({
+        "id": comment.id,
+        "subject_type": comment.subject_type,
+        "subject_id": comment.subject_id,
+        "parent_comment_id": comment.parent_comment_id,
+        "author_id": author_id,
+        "body": body,
+        "deleted": deleted,
+        "created_at": comment.created_at,
+        "updated_at": comment.updated_at
+    })
+}
+
+pub fn thread_details(thread: &Thread) -> Value {
+    let mut details = comment_details(&thread.comment);
+    let replies: Vec<Value> =
+        thread.replies.iter().map(thread_details).collect();
+    details["replies"] = json!(replies);
+    details["more_replies"] = json!(thread.more_replies);
+    details
+}
+
+pub fn thread_page(threads: &[Thread], next_cursor: Option<Uuid>) -> Value {
+    let data: Vec<Value> = threads.iter().map(thread_details).collect();
+    json!({
+        "data": data,
+        "next_cursor": next_cursor
+    })
+}
+
+pub fn comment_create(comment: &Comment) -> Value {
+    json!({ "id": comment.id })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::comment::model::SubjectType;
+
+    use super::*;
+
+    fn fake_comment() -> Comment {
+        Comment {
+            id: Uuid::new_v4(),
+            subject_type: SubjectType::Post,
+            subject_id: Uuid::new_v4(),
+            parent_comment_id: None,
+            author_id: Some(Uuid::new_v4()),
+            body: "hello".to_string(),
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn thread_details_masks_deleted_comments() {
+        let reply = fake_comment();
+        let root = Comment {
+            body: String::new(),
+            deleted_at: Some(Utc::now().naive_utc()),
+            ..fake_comment()
+        };
+        let thread = Thread {
+            comment: root.clone(),
+            replies: vec![Thread {
+                comment: reply.clone(),
+                replies: vec![],
+                more_replies: true,
+            }],
+            more_replies: false,
+        };
+
+        let actual = thread_details(&thread);
+
+        assert_eq!(actual["id"], json!(root.id));
+        assert_eq!(actual["subject_type"], "post");
+        assert_eq!(actual["body"], "[deleted]");
+        assert_eq!(actual["author_id"], Value::Null);
+        assert_eq!(actual["deleted"], true);
+        assert_eq!(actual["replies"][0]["body"], "hello");
+        assert_eq!(actual["replies"][0]["author_id"], json!(reply.author_id));
+        assert_eq!(actual["replies"][0]["more_replies"], true);
+    }
+
+    #[test]
+    fn comment_details_masks_comments_of_purged_authors() {
+        let comment = Comment {
+            author_id: None,
+            ..fake_comment()
+        };
+
+        let actual = comment_details(&comment);
+
+        assert_eq!(actual["body"], "[deleted]");
+        assert_eq!(actual["author_id"], Value::Null);
+        assert_eq!(actual["deleted"], true);
+    }
+}
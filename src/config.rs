@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::feed::strategy::StrategyKind;
+use crate::mailer::smtp::SmtpSecurity;
+use crate::mailer::{MailSettings, MailerKind};
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const MIN_SECRET_LENGTH: usize = 32;
+
+// Upper bounds keep the chrono durations built from these settings, and the
+// timestamps computed from them, far from overflowing.
+const MAX_SESSION_TTL_HOURS: i64 = 24 * 365;
+const MAX_DELETION_GRACE_DAYS: i64 = 365 * 10;
+const MAX_VERIFICATION_TTL_HOURS: i64 = 24 * 365;
+const MAX_PASSWORD_RESET_TTL_MINUTES: i64 = 60 * 24 * 7;
+const MAX_PASSWORD_RESET_INTERVAL_MINUTES: i64 = 60 * 24;
+
+#[derive(Deserialize, PartialEq, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub database_url: String,
+    pub pool_size: u32,
+    pub connection_timeout_secs: u64,
+    pub log_level: String,
+    pub session_secret: String,
+    pub session_ttl_hours: i64,
+    pub shutdown_timeout_secs: u64,
+    pub shutdown_grace_secs: u64,
+    pub migrate_on_startup: bool,
+    pub allow_pending_migrations: bool,
+    pub feed_strategy: StrategyKind,
+    pub deletion_grace_days: i64,
+    pub purge_interval_secs: u64,
+    pub app_url: String,
+    pub verification_ttl_hours: i64,
+    pub password_reset_ttl_minutes: i64,
+    pub password_reset_interval_minutes: i64,
+    pub require_verified_email: bool,
+    pub mailer: MailerKind,
+    pub mail_from: String,
+    pub mail_dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: String,
+    pub smtp_password: String,
+}
+
+/// What the session module needs from the configuration.
+#[derive(PartialEq, Clone, Debug)]
+pub struct SessionSettings {
+    pub secret: String,
+    pub ttl: chrono::Duration,
+    pub require_verified_email: bool,
+}
+
+/// What the account lifecycle endpoints need: how long deleted accounts and
+/// emailed tokens stay valid, how often reset emails may be sent, and where
+/// emailed links should point.
+#[derive(PartialEq, Clone, Debug)]
+pub struct AccountSettings {
+    pub deletion_grace: chrono::Duration,
+    pub app_url: String,
+    pub verification_ttl: chrono::Duration,
+    pub password_reset_ttl: chrono::Duration,
+    pub password_reset_interval: chrono::Duration,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    File(String, String),
+    Parse(String),
+    Env(String, String),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::File(path, reason) => {
+                write!(f, "couldn't read config file {}: {}", path, reason)
+            }
+            ConfigError::Parse(reason) => {
+                write!(f, "invalid config file: {}", reason)
+            }
+            ConfigError::Env(name, reason) => {
+                write!(f, "invalid environment variable {}: {}", name, reason)
+            }
+            ConfigError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+            database_url: String::new(),
+            pool_size: 10,
+            connection_timeout_secs: 30,
+            log_level: "social_net=info,warp=info".to_string(),
+            session_secret: String::new(),
+            session_ttl_hours: 24,
+            shutdown_timeout_secs: 30,
+            shutdown_grace_secs: 5,
+            migrate_on_startup: false,
+            allow_pending_migrations: false,
+            feed_strategy: StrategyKind::Read,
+            deletion_grace_days: 30,
+            purge_interval_secs: 3600,
+            app_url: "http://localhost:8080".to_string(),
+            verification_ttl_hours: 48,
+            password_reset_ttl_minutes: 60,
+            password_reset_interval_minutes: 5,
+            require_verified_email: false,
+            mailer: MailerKind::File,
+            mail_from: "no-reply@localhost".to_string(),
+            mail_dir: "mail".to_string(),
+            smtp_host: "localhost".to_string(),
+            smtp_port: 465,
+            smtp_security: SmtpSecurity::Tls,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `CONFIG_FILE` (or `config.toml` when present), then applies
+    /// environment overrides and validates the result.
+    pub fn load() -> Result<Config, ConfigError> {
+        dotenv::dotenv().ok();
+        let env: HashMap<String, String> = std::env::vars().collect();
+
+        let path = env.get("CONFIG_FILE").cloned();
+        let file = match &path {
+            Some(path) => Some(read_file(path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Some(read_file(DEFAULT_CONFIG_FILE)?)
+            }
+            None => None,
+        };
+
+        Config::from_sources(file.as_deref(), &env)
+    }
+
+    pub fn from_sources(
+        file: Option<&str>,
+        env: &HashMap<String, String>,
+    ) -> Result<Config, ConfigError> {
+        let mut config = match file {
+            Some(contents) => toml::from_str(contents)
+                .map_err(|err| ConfigError::Parse(err.to_string()))?,
+            None => Config::default(),
+        };
+
+        override_from(env, "BIND_ADDRESS", &mut config.bind_address)?;
+        override_from(env, "PORT", &mut config.port)?;
+        override_from(env, "DATABASE_URL", &mut config.database_url)?;
+        override_from(env, "DATABASE_POOL_SIZE", &mut config.pool_size)?;
+        override_from(
+            env,
+            "DATABASE_CONNECTION_TIMEOUT",
+            &mut config.connection_timeout_secs,
+        )?;
+        override_from(env, "RUST_LOG", &mut config.log_level)?;
+        override_from(env, "SESSION_SECRET", &mut config.session_secret)?;
+        override_from(env, "SESSION_TTL_HOURS", &mut config.session_ttl_hours)?;
+        override_from(
+            env,
+            "SHUTDOWN_TIMEOUT",
+            &mut config.shutdown_timeout_secs,
+        )?;
+        override_from(env, "SHUTDOWN_GRACE", &mut config.shutdown_grace_secs)?;
+        override_from(
+            env,
+            "MIGRATE_ON_STARTUP",
+            &mut config.migrate_on_startup,
+        )?;
+        override_from(
+            env,
+            "ALLOW_PENDING_MIGRATIONS",
+            &mut config.allow_pending_migrations,
+        )?;
+        override_from(env, "FEED_STRATEGY", &mut config.feed_strategy)?;
+        override_from(
+            env,
+            "DELETION_GRACE_DAYS",
+            &mut config.deletion_grace_days,
+        )?;
+        override_from(env, "PURGE_INTERVAL", &mut config.purge_interval_secs)?;
+        override_from(env, "APP_URL", &mut config.app_url)?;
+        override_from(
+            env,
+            "VERIFICATION_TTL_HOURS",
+            &mut config.verification_ttl_hours,
+        )?;
+        override_from(
+            env,
+            "PASSWORD_RESET_TTL_MINUTES",
+            &mut config.password_reset_ttl_minutes,
+        )?;
+        override_from(
+            env,
+            "PASSWORD_RESET_INTERVAL_MINUTES",
+            &mut config.password_reset_interval_minutes,
+        )?;
+        override_from(
+            env,
+            "REQUIRE_VERIFIED_EMAIL",
+            &mut config.require_verified_email,
+        )?;
+        override_from(env, "MAILER", &mut config.mailer)?;
+        override_from(env, "MAIL_FROM", &mut config.mail_from)?;
+        override_from(env, "MAIL_DIR", &mut config.mail_dir)?;
+        override_from(env, "SMTP_HOST", &mut config.smtp_host)?;
+        override_from(env, "SMTP_PORT", &mut config.smtp_port)?;
+        override_from(env, "SMTP_SECURITY", &mut config.smtp_security)?;
+        override_from(env, "SMTP_USERNAME", &mut config.smtp_username)?;
+        override_from(env, "SMTP_PASSWORD", &mut config.smtp_password)?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.database_url.is_empty() {
+            return Err(ConfigError::Invalid(
+                "DATABASE_URL must be set".to_string(),
+            ));
+        }
+        if self.pool_size == 0 {
+            return Err(ConfigError::Invalid(
+                "pool_size must be at least 1".to_string(),
+            ));
+        }
+        if self.connection_timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "connection_timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        if self.session_secret.len() < MIN_SECRET_LENGTH {
+            return Err(ConfigError::Invalid(format!(
+                "SESSION_SECRET must be at least {} characters",
+                MIN_SECRET_LENGTH
+            )));
+        }
+        check_range(
+            "session_ttl_hours",
+            self.session_ttl_hours,
+            1,
+            MAX_SESSION_TTL_HOURS,
+        )?;
+        if self.shutdown_grace_secs >= self.shutdown_timeout_secs {
+            return Err(ConfigError::Invalid(
+                "shutdown_grace_secs must be less than shutdown_timeout_secs"
+                    .to_string(),
+            ));
+        }
+        check_range(
+            "deletion_grace_days",
+            self.deletion_grace_days,
+            0,
+            MAX_DELETION_GRACE_DAYS,
+        )?;
+        if self.purge_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "purge_interval_secs must be at least 1".to_string(),
+            ));
+        }
+        check_range(
+            "verification_ttl_hours",
+            self.verification_ttl_hours,
+            1,
+            MAX_VERIFICATION_TTL_HOURS,
+        )?;
+        check_range(
+            "password_reset_ttl_minutes",
+            self.password_reset_ttl_minutes,
+            1,
+            MAX_PASSWORD_RESET_TTL_MINUTES,
+        )?;
+        check_range(
+            "password_reset_interval_minutes",
+            self.password_reset_interval_minutes,
+            0,
+            MAX_PASSWORD_RESET_INTERVAL_MINUTES,
+        )?;
+        if self.mailer == MailerKind::Smtp && self.smtp_host.is_empty() {
+            return Err(ConfigError::Invalid(
+                "SMTP_HOST must be set when MAILER is smtp".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.connection_timeout_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    /// How long to keep accepting connections after readiness starts
+    /// failing, so load balancers notice before the listener closes.
+    pub fn shutdown_grace(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_secs)
+    }
+
+    /// What's left of `shutdown_timeout` for in-flight requests once the
+    /// grace period is over.
+    pub fn drain_timeout(&self) -> Duration {
+        self.shutdown_timeout() - self.shutdown_grace()
+    }
+
+    pub fn purge_interval(&self) -> Duration {
+        Duration::from_secs(self.purge_interval_secs)
+    }
+
+    pub fn account(&self) -> AccountSettings {
+        AccountSettings {
+            deletion_grace: chrono::Duration::days(self.deletion_grace_days),
+            app_url: self.app_url.trim_end_matches('/').to_string(),
+            verification_ttl: chrono::Duration::hours(
+                self.verification_ttl_hours,
+            ),
+            password_reset_ttl: chrono::Duration::minutes(
+                self.password_reset_ttl_minutes,
+            ),
+            password_reset_interval: chrono::Duration::minutes(
+                self.password_reset_interval_minutes,
+            ),
+        }
+    }
+
+    pub fn mail(&self) -> MailSettings {
+        MailSettings {
+            from: self.mail_from.clone(),
+            dir: self.mail_dir.clone(),
+            smtp_host: self.smtp_host.clone(),
+            smtp_port: self.smtp_port,
+            smtp_security: self.smtp_security,
+            smtp_username: self.smtp_username.clone(),
+            smtp_password: self.smtp_password.clone(),
+        }
+    }
+
+    pub fn session(&self) -> SessionSettings {
+        SessionSettings {
+            secret: self.session_secret.clone(),
+            ttl: chrono::Duration::hours(self.session_ttl_hours),
+            require_verified_email: self.require_verified_email,
+        }
+    }
+}
+
+fn read_file(path: &str) -> Result<String, ConfigError> {
+    fs::read_to_string(path)
+        .map_err(|err| ConfigError::File(path.to_string(), err.to_string()))
+}
+
+fn override_from<T>(
+    env: &HashMap<String, String>,
+    name: &str,
+    target: &mut T,
+) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    if let Some(value) = env.get(name) {
+        *target = value.parse().map_err(|err: T::Err| {
+            ConfigError::Env(name.to_string(), err.to_string())
+        })?;
+    }
+    Ok(())
+}
+
+fn check_range(
+    name: &str,
+    value: i64,
+    min: i64,
+    max: i64,
+) -> Result<(), ConfigError> {
+    if value < min || value > max {
+        return Err(ConfigError::Invalid(format!(
+            "{} must be between {} and {}",
+            name, min, max
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn required() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("DATABASE_URL", "postgres://localhost/social_net_dev"),
+            ("SESSION_SECRET", SECRET),
+        ]
+    }
+
+    #[test]
+    fn defaults_apply_when_only_required_values_are_set() {
+        let config = Config::from_sources(None, &env(&required())).unwrap();
+
+        assert_eq!(
+            config.socket_addr(),
+            SocketAddr::from(([127, 0, 0, 1], 8080))
+        );
+        assert_eq!(config.pool_size, 10);
+        assert_eq!(config.connection_timeout(), Duration::from_secs(30));
+        assert_eq!(config.session().ttl, chrono::Duration::hours(24));
+        assert_eq!(config.shutdown_timeout(), Duration::from_secs(30));
+        assert_eq!(config.shutdown_grace(), Duration::from_secs(5));
+        assert_eq!(config.drain_timeout(), Duration::from_secs(25));
+        assert_eq!(config.account().deletion_grace, chrono::Duration::days(30));
+        assert_eq!(config.purge_interval(), Duration::from_secs(3600));
+        assert_eq!(config.mailer, MailerKind::File);
+        assert!(!config.session().require_verified_email);
+    }
+
+    #[test]
+    fn env_overrides_toml_file() {
+        let file = r#"
+            bind_address = "0.0.0.0"
+            port = 3000
+            pool_size = 4
+            database_url = "postgres://file/db"
+        "#;
+        let mut vars = required();
+        vars.push(("PORT", "9090"));
+
+        let config = Config::from_sources(Some(file), &env(&vars)).unwrap();
+
+        assert_eq!(
+            config.socket_addr(),
+            SocketAddr::from(([0, 0, 0, 0], 9090))
+        );
+        assert_eq!(config.pool_size, 4);
+        assert_eq!(config.database_url, "postgres://localhost/social_net_dev");
+    }
+
+    #[test]
+    fn rejects_unparseable_env_values() {
+        let mut vars = required();
+        vars.push(("PORT", "eighty"));
+
+        let err = Config::from_sources(None, &env(&vars)).unwrap_err();
+        assert!(err.to_string().contains("PORT"));
+    }
+
+    #[test]
+    fn rejects_unknown_toml_keys() {
+        let result = Config::from_sources(Some("prot = 80"), &env(&required()));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn requires_database_url_and_strong_session_secret() {
+        let missing_url = env(&[("SESSION_SECRET", SECRET)]);
+        let err = Config::from_sources(None, &missing_url).unwrap_err();
+        assert!(err.to_string().contains("DATABASE_URL"));
+
+        let weak_secret =
+            env(&[("DATABASE_URL", "postgres://db"), ("SESSION_SECRET", "x")]);
+        let err = Config::from_sources(None, &weak_secret).unwrap_err();
+        assert!(err.to_string().contains("SESSION_SECRET"));
+    }
+
+    #[test]
+    fn rejects_empty_pool() {
+        let mut vars = required();
+        vars.push(("DATABASE_POOL_SIZE", "0"));
+
+        assert!(Config::from_sources(None, &env(&vars)).is_err());
+    }
+
+    #[test]
+    fn shutdown_grace_must_fit_inside_shutdown_timeout() {
+        let mut vars = required();
+        vars.push(("SHUTDOWN_TIMEOUT", "10"));
+        vars.push(("SHUTDOWN_GRACE", "10"));
+
+        let err = Config::from_sources(None, &env(&vars)).unwrap_err();
+        assert!(err.to_string().contains("shutdown_grace_secs"));
+    }
+
+    #[test]
+    fn smtp_mailer_requires_host() {
+        let mut vars = required();
+        vars.push(("MAILER", "smtp"));
+        vars.push(("SMTP_HOST", ""));
+
+        let err = Config::from_sources(None, &env(&vars)).unwrap_err();
+        assert!(err.to_string().contains("SMTP_HOST"));
+    }
+
+    #[test]
+    fn durations_must_stay_within_bounds() {
+        let bounds = [
+            (
+                "SESSION_TTL_HOURS",
+                "session_ttl_hours",
+                MAX_SESSION_TTL_HOURS,
+            ),
+            (
+                "DELETION_GRACE_DAYS",
+                "deletion_grace_days",
+                MAX_DELETION_GRACE_DAYS,
+            ),
+            (
+                "VERIFICATION_TTL_HOURS",
+                "verification_ttl_hours",
+                MAX_VERIFICATION_TTL_HOURS,
+            ),
+            (
+                "PASSWORD_RESET_TTL_MINUTES",
+                "password_reset_ttl_minutes",
+                MAX_PASSWORD_RESET_TTL_MINUTES,
+            ),
+            (
+                "PASSWORD_RESET_INTERVAL_MINUTES",
+                "password_reset_interval_minutes",
+                MAX_PASSWORD_RESET_INTERVAL_MINUTES,
+            ),
+        ];
+
+        for &(var, field, max) in &bounds {
+            let largest = max.to_string();
+            let mut vars = required();
+            vars.push((var, &largest));
+            let config = Config::from_sources(None, &env(&vars)).unwrap();
+            config.account();
+            config.session();
+
+            for too_large in &[max + 1, i64::MAX] {
+                let too_large = too_large.to_string();
+                let mut vars = required();
+                vars.push((var, &too_large));
+                let err = Config::from_sources(None, &env(&vars)).unwrap_err();
+                assert!(err.to_string().contains(field), "{}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{Connection, PgConnection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{get, path, post, Filter};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::error::{ApiError, FieldError};
+use crate::filters::json_body;
+use crate::pagination::Cursor;
+use crate::session::handler::{with_auth, with_verified_auth};
+use crate::user::model::User;
+use crate::user::repository::UserRepo;
+use crate::ConnectionPool;
+
+use super::model::{ListParams, NewMessage};
+use super::repository::ConversationRepo;
+use super::{validation, view};
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let conversation_create_route = path!("conversations")
+        .and(post())
+        .and(with_verified_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(json_body())
+        .and_then(conversation_create);
+
+    let conversation_index_route = path!("conversations")
+        .and(get())
+        .and(with_auth(pool.clone(), settings.clone()))
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool.clone()))
+        .and_then(conversation_index);
+
+    let message_create_route = path!("conversations" / Uuid / "messages")
+        .and(post())
+        .and(with_verified_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(json_body())
+        .and_then(message_create);
+
+    let message_index_route = path!("conversations" / Uuid / "messages")
+        .and(get())
+        .and(with_auth(pool.clone(), settings))
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool))
+        .and_then(message_index);
+
+    conversation_create_route
+        .or(conversation_index_route)
+        .or(message_create_route)
+        .or(message_index_route)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConversationRequest {
+    pub member_ids: Vec<Uuid>,
+    pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MessageRequest {
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<Cursor>,
+}
+
+/// Starting a one-to-one conversation that already exists returns it with
+/// 200 instead of opening a second one. The lookup, the new conversation and
+/// its first message commit together.
+async fn conversation_create(
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: ConversationRequest,
+) -> Result<WithStatus<Json>, Infallible> {
+    let req = match validation::validate_new_conversation(req, current_user.id)
+    {
+        Ok(req) => req,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let result = blocking(move || {
+        conn.transaction(|| {
+            for &member_id in &req.member_ids {
+                match UserRepo::find(&conn, member_id) {
+                    Err(diesel::result::Error::NotFound) => {
+                        return Err(ApiError::Validation(vec![
+                            FieldError::new(
+                                "member_ids",
+                                "must reference existing users",
+                            ),
+                        ]));
+                    }
+                    result => result.map(|_| ())?,
+                }
+            }
+
+            let existing = match req.member_ids.as_slice() {
+                [other] => {
+                    ConversationRepo::lock_direct(
+                        &conn,
+                        current_user.id,
+                        *other,
+                    )?;
+                    ConversationRepo::find_direct(
+                        &conn,
+                        current_user.id,
+                        *other,
+                    )?
+                }
+                _ => None,
+            };
+            let (conversation, status) = match existing {
+                Some(conversation) => (conversation, StatusCode::OK),
+                None => (
+                    ConversationRepo::create(
+                        &conn,
+                        current_user.id,
+                        &req.member_ids,
+                    )?,
+                    StatusCode::CREATED,
+                ),
+            };
+
+            if let Some(body) = req.body {
+                let new_message = NewMessage {
+                    conversation_id: conversation.id,
+                    sender_id: current_user.id,
+                    body,
+                };
+                ConversationRepo::send(&conn, new_message)?;
+            }
+            Ok::<_, ApiError>((conversation, status))
+        })
+    })
+    .await;
+
+    match result {
+        Ok((conversation, status)) => {
+            let resp = view::conversation_create(&conversation);
+            Ok(with_status(json(&resp), status))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn conversation_index(
+    current_user: User,
+    query: IndexQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        ConversationRepo::list(&conn, current_user.id, &params)
+    })
+    .await;
+
+    match result {
+        Ok((summaries, next_cursor)) => {
+            let resp = view::conversation_page(&summaries, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn message_create(
+    conversation_id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: MessageRequest,
+) -> Result<WithStatus<Json>, Infallible> {
+    let req = match validation::validate_new_message(req) {
+        Ok(req) => req,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let result = blocking(move || {
+        check_member(&conn, conversation_id, &current_user)?;
+        let new_message = NewMessage {
+            conversation_id,
+            sender_id: current_user.id,
+            body: req.body,
+        };
+        Ok::<_, ApiError>(ConversationRepo::send(&conn, new_message)?)
+    })
+    .await;
+
+    match result {
+        Ok(message) => {
+            let resp = view::message_create(&message);
+            Ok(with_status(json(&resp), StatusCode::CREATED))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn message_index(
+    conversation_id: Uuid,
+    current_user: User,
+    query: IndexQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        check_member(&conn, conversation_id, &current_user)?;
+        Ok::<_, ApiError>(ConversationRepo::messages(
+            &conn,
+            conversation_id,
+            &params,
+        )?)
+    })
+    .await;
+
+    match result {
+        Ok((messages, next_cursor)) => {
+            let resp = view::message_page(&messages, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+/// Only members may read or write a conversation.
+fn check_member(
+    conn: &PgConnection,
+    conversation_id: Uuid,
+    user: &User,
+) -> Result<(), ApiError> {
+    ConversationRepo::find(conn, conversation_id)?;
+    if ConversationRepo::is_member(conn, conversation_id, user.id)? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use warp::Reply;
+
+    use crate::error::handle_rejection;
+    use crate::test_helpers::{
+        create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    async fn body_json(resp: WithStatus<Json>) -> (StatusCode, Value) {
+        let (parts, body) = resp.into_response().into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (parts.status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn start(
+        pool: &ConnectionPool,
+        creator: &User,
+        members: &[&User],
+        body: Option<&str>,
+    ) -> (StatusCode, Value) {
+        let req = ConversationRequest {
+            member_ids: members.iter().map(|user| user.id).collect(),
+            body: body.map(str::to_string),
+        };
+        let resp =
+            conversation_create(creator.clone(), pool.get().unwrap(), req)
+                .await
+                .unwrap();
+        body_json(resp).await
+    }
+
+    #[tokio::test]
+    async fn conversation_create_reuses_direct_conversation() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        drop(conn);
+
+        let (status, first) = start(&pool, &bob, &[&alice], Some("hi")).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, second) = start(&pool, &alice, &[&bob], None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(first["id"], second["id"]);
+
+        let resp = conversation_index(
+            alice,
+            IndexQuery::default(),
+            pool.get().unwrap(),
+        )
+        .await
+        .unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["id"], first["id"]);
+        assert_eq!(body["data"][0]["last_message"]["preview"], "hi");
+    }
+
+    #[tokio::test]
+    async fn conversation_create_rejects_unknown_members() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+        let req = ConversationRequest {
+            member_ids: vec![Uuid::new_v4()],
+            body: None,
+        };
+
+        let resp = conversation_create(bob, pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        let (status, body) = body_json(resp).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["field"], "member_ids");
+    }
+
+    #[tokio::test]
+    async fn messages_are_restricted_to_members() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        drop(conn);
+
+        let (_, conversation) = start(&pool, &bob, &[&alice], None).await;
+        let id: Uuid =
+            serde_json::from_value(conversation["id"].clone()).unwrap();
+
+        let req = MessageRequest {
+            body: "hello".to_string(),
+        };
+        let resp = message_create(id, alice.clone(), pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::CREATED);
+
+        let req = MessageRequest {
+            body: "let me in".to_string(),
+        };
+        let resp = message_create(id, eve.clone(), pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::FORBIDDEN);
+
+        let resp =
+            message_index(id, eve, IndexQuery::default(), pool.get().unwrap())
+                .await
+                .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::FORBIDDEN);
+
+        let resp =
+            message_index(id, bob, IndexQuery::default(), pool.get().unwrap())
+                .await
+                .unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["body"], "hello");
+        assert_eq!(body["data"][0]["sender_id"], json!(alice.id));
+    }
+
+    #[tokio::test]
+    async fn conversation_routes_require_auth() {
+        let filter = routes(establish_connection(), session_settings())
+            .recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("GET")
+            .path("/conversations")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
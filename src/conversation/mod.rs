@@ -0,0 +1,5 @@
+pub mod handler;
+pub mod model;
+pub mod repository;
+mod validation;
+mod view;
@@ -0,0 +1,74 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+use crate::schema::{conversation_members, messages};
+use crate::user::model::{User, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use super::handler::IndexQuery;
+
+/// Upper bound on members, the creator included, so group conversations
+/// stay small.
+pub const MAX_MEMBERS: usize = 10;
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct Conversation {
+    pub id: Uuid,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub last_message_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "conversation_members"]
+pub struct NewMember {
+    pub conversation_id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Queryable, QueryableByName, PartialEq, Clone, Debug)]
+#[table_name = "messages"]
+pub struct Message {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "messages"]
+pub struct NewMessage {
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub body: String,
+}
+
+/// A conversation as it appears in a member's inbox.
+#[derive(PartialEq, Clone, Debug)]
+pub struct ConversationSummary {
+    pub conversation: Conversation,
+    pub members: Vec<User>,
+    pub last_message: Option<Message>,
+}
+
+/// Conversations run most recently active first and messages newest first;
+/// `cursor` is the position of the last item already seen.
+#[derive(PartialEq, Debug)]
+pub struct ListParams {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+}
+
+impl From<IndexQuery> for ListParams {
+    fn from(query: IndexQuery) -> Self {
+        ListParams {
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            cursor: query.cursor,
+        }
+    }
+}
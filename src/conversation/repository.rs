@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use diesel::sql_types;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::{into_page, Cursor};
+use crate::schema::{conversation_members, conversations, messages, users};
+use crate::user::model::User;
+use crate::user::repository::active;
+
+use super::model::{
+    Conversation, ConversationSummary, ListParams, Message, NewMember,
+    NewMessage,
+};
+
+pub struct ConversationRepo;
+
+impl ConversationRepo {
+    /// Starts a conversation between `creator` and every id in `members`.
+    pub fn create(
+        conn: &PgConnection,
+        creator: Uuid,
+        members: &[Uuid],
+    ) -> QueryResult<Conversation> {
+        conn.transaction(|| {
+            let conversation: Conversation =
+                diesel::insert_into(conversations::table)
+                    .default_values()
+                    .get_result(conn)?;
+            let new_members: Vec<NewMember> = std::iter::once(creator)
+                .chain(members.iter().copied())
+                .map(|user_id| NewMember {
+                    conversation_id: conversation.id,
+                    user_id,
+                })
+                .collect();
+            diesel::insert_into(conversation_members::table)
+                .values(&new_members)
+                .execute(conn)?;
+            Ok(conversation)
+        })
+    }
+
+    pub fn find(
+        conn: &PgConnection,
+        conversation_id: Uuid,
+    ) -> QueryResult<Conversation> {
+        conversations::table.find(conversation_id).first(conn)
+    }
+
+    /// The existing one-to-one conversation between two users, if any, so
+    /// starting a chat twice lands in the same thread.
+    pub fn find_direct(
+        conn: &PgConnection,
+        user_id: Uuid,
+        other_id: Uuid,
+    ) -> QueryResult<Option<Conversation>> {
+        // Diesel 1.4 can't alias a table, so the self-join runs in two steps.
+        let own: Vec<Uuid> = conversation_members::table
+            .filter(conversation_members::user_id.eq(user_id))
+            .select(conversation_members::conversation_id)
+            .load(conn)?;
+        let shared: Vec<Uuid> = conversation_members::table
+            .filter(conversation_members::user_id.eq(other_id))
+            .filter(conversation_members::conversation_id.eq_any(&own))
+            .select(conversation_members::conversation_id)
+            .load(conn)?;
+
+        let mut sizes: HashMap<Uuid, usize> = HashMap::new();
+        for conversation_id in conversation_members::table
+            .filter(conversation_members::conversation_id.eq_any(&shared))
+            .select(conversation_members::conversation_id)
+            .load::<Uuid>(conn)?
+        {
+            *sizes.entry(conversation_id).or_default() += 1;
+        }
+
+        match sizes.into_iter().find(|(_, size)| *size == 2) {
+            Some((conversation_id, _)) => {
+                Self::find(conn, conversation_id).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Serializes starting a one-to-one conversation between the same two
+    /// users, whichever of them starts it, so concurrent requests can't both
+    /// miss `find_direct` and open two threads. Held until the surrounding
+    /// transaction ends.
+    pub fn lock_direct(
+        conn: &PgConnection,
+        user_id: Uuid,
+        other_id: Uuid,
+    ) -> QueryResult<()> {
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+            .bind::<sql_types::BigInt, _>(direct_lock_key(user_id, other_id))
+            .execute(conn)
+            .map(|_| ())
+    }
+
+    pub fn is_member(
+        conn: &PgConnection,
+        conversation_id: Uuid,
+        user_id: Uuid,
+    ) -> QueryResult<bool> {
+        diesel::select(exists(
+            conversation_members::table.find((conversation_id, user_id)),
+        ))
+        .get_result(conn)
+    }
+
+    /// Stores the message and bumps the conversation to the top of its
+    /// members' inboxes.
+    pub fn send(
+        conn: &PgConnection,
+        new_message: NewMessage,
+    ) -> QueryResult<Message> {
+        conn.transaction(|| {
+            let message: Message = diesel::insert_into(messages::table)
+                .values(new_message)
+                .get_result(conn)?;
+            diesel::update(conversations::table.find(message.conversation_id))
+                .set(conversations::last_message_at.eq(message.created_at))
+                .execute(conn)?;
+            Ok(message)
+        })
+    }
+
+    /// The user's conversations, most recently active first, with their
+    /// active members and latest message. The second value is the cursor for the
+    /// following page, if any.
+    pub fn list(
+        conn: &PgConnection,
+        user_id: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<ConversationSummary>, Option<Cursor>)> {
+        let mut query = conversations::table
+            .filter(
+                conversations::id.eq_any(
+                    conversation_members::table
+                        .filter(conversation_members::user_id.eq(user_id))
+                        .select(conversation_members::conversation_id),
+                ),
+            )
+            .order((
+                conversations::last_message_at.desc(),
+                conversations::id.desc(),
+            ))
+            .into_boxed();
+
+        if let Some(cursor) = params.cursor {
+            query = query.filter(
+                conversations::last_message_at.lt(cursor.at).or(
+                    conversations::last_message_at
+                        .eq(cursor.at)
+                        .and(conversations::id.lt(cursor.id)),
+                ),
+            );
+        }
+
+        let page = query.limit(params.limit + 1).load::<Conversation>(conn)?;
+        let (page, next_cursor) = into_page(page, params.limit, |c| {
+            Cursor::new(c.last_message_at, c.id)
+        });
+
+        let ids: Vec<Uuid> = page.iter().map(|c| c.id).collect();
+        let mut members: HashMap<Uuid, Vec<User>> = HashMap::new();
+        for (conversation_id, user) in conversation_members::table
+            .inner_join(users::table)
+            .filter(conversation_members::conversation_id.eq_any(&ids))
+            .filter(active())
+            .order(users::username.asc())
+            .select((conversation_members::conversation_id, users::all_columns))
+            .load::<(Uuid, User)>(conn)?
+        {
+            members.entry(conversation_id).or_default().push(user);
+        }
+        let mut last_messages: HashMap<Uuid, Message> =
+            Self::last_messages(conn, &ids)?
+                .into_iter()
+                .map(|message| (message.conversation_id, message))
+                .collect();
+
+        let summaries = page
+            .into_iter()
+            .map(|conversation| ConversationSummary {
+                members: members.remove(&conversation.id).unwrap_or_default(),
+                last_message: last_messages.remove(&conversation.id),
+                conversation,
+            })
+            .collect();
+        Ok((summaries, next_cursor))
+    }
+
+    /// Message history, newest first. The second value is the cursor for
+    /// the following page, if any.
+    pub fn messages(
+        conn: &PgConnection,
+        conversation_id: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<Message>, Option<Cursor>)> {
+        let mut query = messages::table
+            .filter(messages::conversation_id.eq(conversation_id))
+            .order((messages::created_at.desc(), messages::id.desc()))
+            .into_boxed();
+
+        if let Some(cursor) = params.cursor {
+            query = query.filter(
+                messages::created_at.lt(cursor.at).or(messages::created_at
+                    .eq(cursor.at)
+                    .and(messages::id.lt(cursor.id))),
+            );
+        }
+
+        let page = query.limit(params.limit + 1).load::<Message>(conn)?;
+        Ok(into_page(page, params.limit, |message| {
+            Cursor::new(message.created_at, message.id)
+        }))
+    }
+
+    /// Diesel 1.4 has no `DISTINCT ON`, so the latest message per
+    /// conversation is fetched with raw SQL.
+    fn last_messages(
+        conn: &PgConnection,
+        conversation_ids: &[Uuid],
+    ) -> QueryResult<Vec<Message>> {
+        diesel::sql_query(
+            "SELECT DISTINCT ON (conversation_id) * FROM messages \
+             WHERE conversation_id = ANY($1) \
+             ORDER BY conversation_id, created_at DESC, id DESC",
+        )
+        .bind::<sql_types::Array<sql_types::Uuid>, _>(conversation_ids)
+        .load(conn)
+    }
+}
+
+fn direct_lock_key(user_id: Uuid, other_id: Uuid) -> i64 {
+    let (low, high) = if user_id < other_id {
+        (user_id, other_id)
+    } else {
+        (other_id, user_id)
+    };
+    (low.as_u128() ^ high.as_u128().rotate_left(64)) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use crate::test_helpers::{create_user, establish_connection};
+    use crate::user::repository::UserRepo;
+
+    use super::*;
+
+    fn send(
+        conn: &PgConnection,
+        conversation: &Conversation,
+        sender: &User,
+        body: &str,
+    ) -> Message {
+        let new_message = NewMessage {
+            conversation_id: conversation.id,
+            sender_id: sender.id,
+            body: body.to_string(),
+        };
+        ConversationRepo::send(conn, new_message)
+            .expect("Failed to send message")
+    }
+
+    #[test]
+    fn find_direct_ignores_group_conversations() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+
+        ConversationRepo::create(&conn, bob.id, &[alice.id, eve.id]).unwrap();
+        assert_eq!(
+            ConversationRepo::find_direct(&conn, bob.id, alice.id),
+            Ok(None)
+        );
+
+        let direct =
+            ConversationRepo::create(&conn, alice.id, &[bob.id]).unwrap();
+        assert_eq!(
+            ConversationRepo::find_direct(&conn, bob.id, alice.id),
+            Ok(Some(direct))
+        );
+    }
+
+    #[test]
+    fn lock_direct_holds_the_pair_in_either_order() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let other = pool.get().unwrap();
+        let (bob, alice) = (Uuid::new_v4(), Uuid::new_v4());
+        let try_lock = |user_id, other_id| {
+            diesel::sql_query("SELECT pg_try_advisory_xact_lock($1) AS locked")
+                .bind::<sql_types::BigInt, _>(direct_lock_key(
+                    user_id, other_id,
+                ))
+                .get_result::<Locked>(&other)
+                .unwrap()
+                .locked
+        };
+
+        ConversationRepo::lock_direct(&conn, bob, alice).unwrap();
+
+        assert!(!try_lock(alice, bob));
+        assert!(try_lock(bob, Uuid::new_v4()));
+    }
+
+    #[derive(QueryableByName)]
+    struct Locked {
+        #[sql_type = "sql_types::Bool"]
+        locked: bool,
+    }
+
+    #[test]
+    fn list_includes_members_and_last_message() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        let conversation =
+            ConversationRepo::create(&conn, bob.id, &[alice.id]).unwrap();
+        let message = send(&conn, &conversation, &alice, "hi bob");
+
+        let params = ListParams {
+            limit: 20,
+            cursor: None,
+        };
+        let (summaries, next_cursor) =
+            ConversationRepo::list(&conn, bob.id, &params).unwrap();
+
+        assert_eq!(next_cursor, None);
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].conversation.id, conversation.id);
+        assert_eq!(summaries[0].members.len(), 2);
+        assert_eq!(summaries[0].last_message, Some(message));
+
+        let (summaries, _) =
+            ConversationRepo::list(&conn, eve.id, &params).unwrap();
+        assert!(summaries.is_empty());
+    }
+
+    #[test]
+    fn list_resumes_after_cursor_conversation_gets_a_message() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let mut started = Vec::new();
+        for hours in 1..=3 {
+            let conversation =
+                ConversationRepo::create(&conn, bob.id, &[alice.id]).unwrap();
+            diesel::update(conversations::table.find(conversation.id))
+                .set(
+                    conversations::last_message_at
+                        .eq(Utc::now().naive_utc() - Duration::hours(hours)),
+                )
+                .execute(&conn)
+                .unwrap();
+            started.push(conversation);
+        }
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+        };
+        let (first, cursor) =
+            ConversationRepo::list(&conn, bob.id, &params).unwrap();
+        send(&conn, &first[1].conversation, &alice, "bumped");
+        let params = ListParams { limit: 2, cursor };
+        let (second, cursor) =
+            ConversationRepo::list(&conn, bob.id, &params).unwrap();
+
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].conversation.id, started[2].id);
+        assert_eq!(cursor, None);
+    }
+
+    #[test]
+    fn messages_pages_history() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let conversation =
+            ConversationRepo::create(&conn, bob.id, &[alice.id]).unwrap();
+        send(&conn, &conversation, &bob, "one");
+        send(&conn, &conversation, &alice, "two");
+
+        let params = ListParams {
+            limit: 1,
+            cursor: None,
+        };
+        let (first, next_cursor) =
+            ConversationRepo::messages(&conn, conversation.id, &params)
+                .unwrap();
+        assert_eq!(
+            next_cursor,
+            Some(Cursor::new(first[0].created_at, first[0].id))
+        );
+
+        let params = ListParams {
+            cursor: next_cursor,
+            ..params
+        };
+        let (second, next_cursor) =
+            ConversationRepo::messages(&conn, conversation.id, &params)
+                .unwrap();
+        assert_eq!(next_cursor, None);
+        assert_ne!(first[0].id, second[0].id);
+    }
+
+    #[test]
+    fn is_member_checks_membership() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        let conversation =
+            ConversationRepo::create(&conn, bob.id, &[alice.id]).unwrap();
+
+        assert_eq!(
+            ConversationRepo::is_member(&conn, conversation.id, alice.id),
+            Ok(true)
+        );
+        assert_eq!(
+            ConversationRepo::is_member(&conn, conversation.id, eve.id),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn list_skips_inactive_members() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        ConversationRepo::create(&conn, bob.id, &[alice.id, eve.id]).unwrap();
+        UserRepo::deactivate(&conn, eve.id).unwrap();
+
+        let params = ListParams {
+            limit: 20,
+            cursor: None,
+        };
+        let (summaries, _) =
+            ConversationRepo::list(&conn, bob.id, &params).unwrap();
+
+        let members: Vec<Uuid> =
+            summaries[0].members.iter().map(|user| user.id).collect();
+        assert_eq!(members.len(), 2);
+        assert!(!members.contains(&eve.id));
+    }
+}
@@ -0,0 +1,122 @@
+use uuid::Uuid;
+
+use crate::error::{ApiError, FieldError};
+
+use super::handler::{ConversationRequest, MessageRequest};
+use super::model::MAX_MEMBERS;
+
+const BODY_MAX_LENGTH: usize = 2000;
+
+/// Drops the creator and duplicates from `member_ids`, then checks the
+/// group size and the optional opening message.
+pub fn validate_new_conversation(
+    req: ConversationRequest,
+    creator: Uuid,
+) -> Result<ConversationRequest, ApiError> {
+    let mut member_ids = Vec::with_capacity(req.member_ids.len());
+    for id in req.member_ids {
+        if id != creator && !member_ids.contains(&id) {
+            member_ids.push(id);
+        }
+    }
+
+    let mut errors = Vec::new();
+    if member_ids.is_empty() {
+        errors.push(FieldError::new(
+            "member_ids",
+            "must include at least one other user",
+        ));
+    } else if member_ids.len() >= MAX_MEMBERS {
+        errors.push(FieldError::new(
+            "member_ids",
+            format!("must include at most {} other users", MAX_MEMBERS - 1),
+        ));
+    }
+
+    let body = match req.body {
+        Some(body) => match validate_body(&body) {
+            Ok(body) => Some(body),
+            Err(err) => {
+                errors.push(err);
+                None
+            }
+        },
+        None => None,
+    };
+
+    if errors.is_empty() {
+        Ok(ConversationRequest { member_ids, body })
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
+
+pub fn validate_new_message(
+    req: MessageRequest,
+) -> Result<MessageRequest, ApiError> {
+    match validate_body(&req.body) {
+        Ok(body) => Ok(MessageRequest { body }),
+        Err(err) => Err(ApiError::Validation(vec![err])),
+    }
+}
+
+/// Trims the message body and rejects it when empty or too long.
+fn validate_body(body: &str) -> Result<String, FieldError> {
+    let body = body.trim().to_string();
+    let length = body.chars().count();
+
+    if length == 0 {
+        Err(FieldError::new("body", "must not be blank"))
+    } else if length > BODY_MAX_LENGTH {
+        Err(FieldError::new(
+            "body",
+            format!("must be at most {} characters", BODY_MAX_LENGTH),
+        ))
+    } else {
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_new_conversation_dedupes_members() {
+        let creator = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let req = ConversationRequest {
+            member_ids: vec![other, creator, other],
+            body: Some(" hi ".to_string()),
+        };
+
+        let actual = validate_new_conversation(req, creator).unwrap();
+
+        assert_eq!(actual.member_ids, vec![other]);
+        assert_eq!(actual.body.as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn validate_new_conversation_bounds_group_size() {
+        let creator = Uuid::new_v4();
+        let alone = ConversationRequest {
+            member_ids: vec![creator],
+            body: None,
+        };
+        assert!(validate_new_conversation(alone, creator).is_err());
+
+        let crowd = ConversationRequest {
+            member_ids: (0..MAX_MEMBERS).map(|_| Uuid::new_v4()).collect(),
+            body: None,
+        };
+        assert!(validate_new_conversation(crowd, creator).is_err());
+    }
+
+    #[test]
+    fn validate_new_message_rejects_blank_body() {
+        let req = MessageRequest {
+            body: "  ".to_string(),
+        };
+        assert!(validate_new_message(req).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use serde_json::{json, Value};
+
+use crate::pagination::Cursor;
+
+use super::model::{Conversation, ConversationSummary, Message};
+
+const PREVIEW_LENGTH: usize = 100;
+
+pub fn message_details(message: &Message) -> Value {
+    json!({
+        "id": message.id,
+        "conversation_id": message.conversation_id,
+        "sender_id": message.sender_id,
+        "body": message.body,
+        "created_at": message.created_at
+    })
+}
+
+pub fn message_page(
+    messages: &[Message],
+    next_cursor: Option<Cursor>,
+) -> Value {
+    let data: Vec<Value> = messages.iter().map(message_details).collect();
+    json!({
+        "data": data,
+        "next_cursor": next_cursor
+    })
+}
+
+/// Inbox entries carry only the start of the latest message.
+pub fn conversation_page(
+    summaries: &[ConversationSummary],
+    next_cursor: Option<Cursor>,
+) -> Value {
+    let data: Vec<Value> = summaries
+        .iter()
+        .map(|summary| {
+            let members: Vec<Value> = summary
+                .members
+                .iter()
+                .map(|user| json!({ "id": user.id, "username": user.username }))
+                .collect();
+            let last_message = summary.last_message.as_ref().map(|message| {
+                json!({
+                    "id": message.id,
+                    "sender_id": message.sender_id,
+                    "preview": preview(&message.body),
+                    "created_at": message.created_at
+                })
+            });
+            json!({
+                "id": summary.conversation.id,
+                "members": members,
+                "last_message": last_message,
+                "last_message_at": summary.conversation.last_message_at
+            })
+        })
+        .collect();
+
+    json!({
+        "data": data,
+        "next_cursor": next_cursor
+    })
+}
+
+pub fn conversation_create(conversation: &Conversation) -> Value {
+    json!({ "id": conversation.id })
+}
+
+pub fn message_create(message: &Message) -> Value {
+    json!({ "id": message.id })
+}
+
+fn preview(body: &str) -> String {
+    if body.chars().count() > PREVIEW_LENGTH {
+        let cut: String = body.chars().take(PREVIEW_LENGTH).collect();
+        format!("{}…", cut)
+    } else {
+        body.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn conversation_page_truncates_preview() {
+        let conversation = Conversation {
+            id: Uuid::new_v4(),
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            last_message_at: Utc::now().naive_utc(),
+        };
+        let message = Message {
+            id: Uuid::new_v4(),
+            conversation_id: conversation.id,
+            sender_id: Uuid::new_v4(),
+            body: "a".repeat(150),
+            created_at: conversation.last_message_at,
+        };
+        let summary = ConversationSummary {
+            conversation,
+            members: vec![],
+            last_message: Some(message),
+        };
+
+        let actual = conversation_page(&[summary], None);
+
+        let preview = actual["data"][0]["last_message"]["preview"]
+            .as_str()
+            .unwrap();
+        assert_eq!(preview, format!("{}…", "a".repeat(100)));
+        assert_eq!(actual["next_cursor"], Value::Null);
+    }
+}
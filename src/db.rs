@@ -0,0 +1,172 @@
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::PgConnection;
+use tokio::task;
+use warp::{Filter, Rejection};
+
+use crate::error::ApiError;
+use crate::ConnectionPool;
+
+/// Checks a connection out of the pool, rejecting with
+/// `ApiError::ServiceUnavailable` when the pool is exhausted or Postgres is
+/// unreachable. Checkout can wait for the pool timeout, so it runs on the
+/// blocking thread pool like every other Diesel call.
+pub fn with_db_conn(
+    pool: ConnectionPool,
+) -> impl Filter<
+    Extract = (PooledConnection<ConnectionManager<PgConnection>>,),
+    Error = Rejection,
+> + Clone {
+    warp::any().and_then(move || {
+        let pool = pool.clone();
+        async move {
+            blocking(move || {
+                pool.get().map_err(|err| {
+                    error!("Couldn't get a database connection: {}", err);
+                    ApiError::ServiceUnavailable
+                })
+            })
+            .await
+            .map_err(warp::reject::custom)
+        }
+    })
+}
+
+/// Runs synchronous Diesel work on tokio's blocking pool so slow queries and
+/// password hashing don't stall the async workers serving other routes.
+pub async fn blocking<F, T, E>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: Into<ApiError> + Send + 'static,
+{
+    match task::spawn_blocking(f).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(err) => {
+            error!("Blocking database task failed: {}", err);
+            Err(ApiError::Internal)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use diesel::{sql_query, QueryResult, RunQueryDsl};
+    use warp::test::request;
+
+    use crate::ping;
+    use crate::shutdown::Shutdown;
+    use crate::test_helpers::{
+        establish_connection, establish_connection_with, unreachable_pool,
+    };
+
+    use super::*;
+
+    const SLOW_QUERIES: usize = 3;
+    const SLOW_QUERY_SECONDS: f64 = 0.2;
+
+    fn slow_query(pool: &ConnectionPool) -> QueryResult<usize> {
+        let conn = pool.get().unwrap();
+        sql_query(format!("select pg_sleep({})", SLOW_QUERY_SECONDS))
+            .execute(&conn)
+    }
+
+    /// Queues a /ping request behind whatever tasks are already scheduled.
+    async fn ping_latency(pool: ConnectionPool) -> Duration {
+        let start = Instant::now();
+        let resp = tokio::spawn(async move {
+            request()
+                .path("/ping")
+                .reply(&ping::routes(pool, Shutdown::default()))
+                .await
+        })
+        .await
+        .unwrap();
+        assert!(resp.status().is_success());
+        start.elapsed()
+    }
+
+    #[tokio::test]
+    async fn blocking_returns_closure_result() {
+        let result = blocking(|| Ok::<_, ApiError>(42)).await;
+        assert_eq!(result, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn blocking_converts_diesel_errors() {
+        let result: Result<(), ApiError> =
+            blocking(|| Err(diesel::result::Error::NotFound)).await;
+        assert_eq!(result, Err(ApiError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn with_db_conn_yields_connection() {
+        let result = request()
+            .filter(&with_db_conn(establish_connection()))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn with_db_conn_rejects_when_pool_is_exhausted() {
+        let pool = establish_connection_with(1, Duration::from_millis(50));
+        let _held = pool.get().unwrap();
+
+        let result = request().filter(&with_db_conn(pool.clone())).await;
+
+        assert_eq!(
+            result.err().unwrap().find::<ApiError>(),
+            Some(&ApiError::ServiceUnavailable)
+        );
+    }
+
+    #[tokio::test]
+    async fn with_db_conn_rejects_when_database_is_unreachable() {
+        let result = request().filter(&with_db_conn(unreachable_pool())).await;
+
+        assert_eq!(
+            result.err().unwrap().find::<ApiError>(),
+            Some(&ApiError::ServiceUnavailable)
+        );
+    }
+
+    /// `#[tokio::test]` runs a single-threaded scheduler, which makes the
+    /// effect of blocking a worker obvious. Run with
+    /// `cargo test bench_ -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore]
+    async fn bench_ping_latency_during_slow_queries() {
+        let pool = establish_connection_with(
+            SLOW_QUERIES as u32,
+            Duration::from_secs(5),
+        );
+
+        let mut handles = Vec::new();
+        for _ in 0..SLOW_QUERIES {
+            let pool = pool.clone();
+            handles.push(tokio::spawn(async move { slow_query(&pool) }));
+        }
+        let inline = ping_latency(pool.clone()).await;
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        let mut handles = Vec::new();
+        for _ in 0..SLOW_QUERIES {
+            let pool = pool.clone();
+            handles.push(tokio::spawn(blocking(move || slow_query(&pool))));
+        }
+        let offloaded = ping_latency(pool.clone()).await;
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        println!(
+            "/ping latency with {} concurrent {}s queries: inline {:?}, spawn_blocking {:?}",
+            SLOW_QUERIES, SLOW_QUERY_SECONDS, inline, offloaded
+        );
+        assert!(offloaded < inline);
+        assert!(offloaded < Duration::from_secs_f64(SLOW_QUERY_SECONDS));
+    }
+}
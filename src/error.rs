@@ -0,0 +1,390 @@
+use std::convert::Infallible;
+
+use diesel::result::{DatabaseErrorKind, Error};
+use serde::Serialize;
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::reject::Reject;
+use warp::reply::{json, with_header, with_status, Json, WithStatus};
+use warp::{Rejection, Reply};
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized,
+    InvalidCredentials,
+    Forbidden,
+    IncorrectPassword,
+    EmailNotVerified,
+    NotFound,
+    MethodNotAllowed,
+    Conflict(Option<String>),
+    Validation(Vec<FieldError>),
+    ServiceUnavailable,
+    Internal,
+}
+
+/// Hint sent with 503s so clients and load balancers back off briefly.
+pub const RETRY_AFTER_SECONDS: u64 = 5;
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Reject for ApiError {}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized | ApiError::InvalidCredentials => {
+                StatusCode::UNAUTHORIZED
+            }
+            ApiError::Forbidden
+            | ApiError::IncorrectPassword
+            | ApiError::EmailNotVerified => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Forbidden => "forbidden",
+            ApiError::IncorrectPassword => "incorrect_password",
+            ApiError::EmailNotVerified => "email_not_verified",
+            ApiError::NotFound => "not_found",
+            ApiError::MethodNotAllowed => "method_not_allowed",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::ServiceUnavailable => "service_unavailable",
+            ApiError::Internal => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::BadRequest(message) => message.clone(),
+            ApiError::Unauthorized => "Missing or invalid bearer token".into(),
+            ApiError::InvalidCredentials => "Invalid login or password".into(),
+            ApiError::Forbidden => {
+                "You are not allowed to perform this action".into()
+            }
+            ApiError::IncorrectPassword => {
+                "Current password is incorrect".into()
+            }
+            ApiError::EmailNotVerified => {
+                "Verify your email address first".into()
+            }
+            ApiError::NotFound => "Resource not found".into(),
+            ApiError::MethodNotAllowed => "Method not allowed".into(),
+            ApiError::Conflict(Some(field)) => {
+                format!("{} is already taken", field)
+            }
+            ApiError::Conflict(None) => "Resource already exists".into(),
+            ApiError::Validation(errors) => match errors.as_slice() {
+                [error] => format!("{} {}", error.field, error.message),
+                _ => "Request validation failed".into(),
+            },
+            ApiError::ServiceUnavailable => {
+                "Service temporarily unavailable, please retry".into()
+            }
+            ApiError::Internal => "Something went wrong".into(),
+        }
+    }
+
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            ApiError::IncorrectPassword => Some("current_password"),
+            ApiError::Conflict(field) => field.as_deref(),
+            ApiError::Validation(errors) => {
+                errors.first().map(|error| error.field.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn reply(&self) -> WithStatus<Json> {
+        let mut body = json!({
+            "error": {
+                "code": self.code(),
+                "message": self.message(),
+                "field": self.field()
+            }
+        });
+        if let ApiError::Validation(errors) = self {
+            body["error"]["details"] = json!(errors);
+        }
+        with_status(json(&body), self.status())
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::NotFound => ApiError::NotFound,
+            Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                let field = match (info.table_name(), info.constraint_name()) {
+                    (Some(table), Some(constraint)) => {
+                        constraint_field(table, constraint)
+                    }
+                    _ => None,
+                };
+                ApiError::Conflict(field)
+            }
+            err => {
+                error!("Unexpected database error: {}", err);
+                ApiError::Internal
+            }
+        }
+    }
+}
+
+/// Postgres names unique constraints `<table>_<column>_key` by default.
+/// Both table and column names may contain underscores, so the table
+/// reported with the violation is stripped rather than split on.
+fn constraint_field(table: &str, constraint: &str) -> Option<String> {
+    constraint
+        .strip_prefix(table)?
+        .strip_prefix('_')?
+        .strip_suffix("_key")
+        .filter(|column| !column.is_empty())
+        .map(str::to_string)
+}
+
+pub async fn handle_rejection(
+    err: Rejection,
+) -> Result<impl Reply, Infallible> {
+    let api_error = if let Some(api_error) = err.find::<ApiError>() {
+        api_error.clone()
+    } else if let Some(body_error) =
+        err.find::<warp::filters::body::BodyDeserializeError>()
+    {
+        ApiError::BadRequest(body_error.to_string())
+    } else if let Some(query_error) = err.find::<warp::reject::InvalidQuery>() {
+        ApiError::BadRequest(query_error.to_string())
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        ApiError::MethodNotAllowed
+    } else if err.is_not_found() {
+        ApiError::NotFound
+    } else {
+        error!("Unhandled rejection: {:?}", err);
+        ApiError::Internal
+    };
+
+    let resp = api_error.reply().into_response();
+    match api_error {
+        ApiError::Unauthorized => {
+            Ok(with_header(resp, "www-authenticate", "Bearer").into_response())
+        }
+        ApiError::ServiceUnavailable => Ok(with_header(
+            resp,
+            "retry-after",
+            RETRY_AFTER_SECONDS.to_string(),
+        )
+        .into_response()),
+        _ => Ok(resp),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use warp::{get, path, Filter};
+
+    use super::*;
+
+    fn body_json(body: &[u8]) -> Value {
+        serde_json::from_slice(body).expect("Invalid response")
+    }
+
+    #[test]
+    fn constraint_field_extracts_column_name() {
+        assert_eq!(
+            constraint_field("users", "users_username_key"),
+            Some("username".to_string())
+        );
+        assert_eq!(
+            constraint_field("sessions", "sessions_token_hash_key"),
+            Some("token_hash".to_string())
+        );
+        assert_eq!(constraint_field("users", "users_pkey"), None);
+    }
+
+    #[test]
+    fn constraint_field_handles_underscored_table_names() {
+        assert_eq!(
+            constraint_field(
+                "password_resets",
+                "password_resets_token_hash_key"
+            ),
+            Some("token_hash".to_string())
+        );
+        assert_eq!(
+            constraint_field(
+                "email_verifications",
+                "email_verifications_token_hash_key"
+            ),
+            Some("token_hash".to_string())
+        );
+        assert_eq!(constraint_field("users", "sessions_token_hash_key"), None);
+    }
+
+    #[test]
+    fn not_found_diesel_error_maps_to_not_found() {
+        assert_eq!(ApiError::from(Error::NotFound), ApiError::NotFound);
+    }
+
+    #[tokio::test]
+    async fn reply_renders_error_envelope() {
+        let resp = ApiError::Conflict(Some("email".to_string()))
+            .reply()
+            .into_response();
+        let status = resp.status();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+
+        let expected = json!({
+            "error": {
+                "code": "conflict",
+                "message": "email is already taken",
+                "field": "email"
+            }
+        });
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body_json(&body), expected);
+    }
+
+    #[tokio::test]
+    async fn reply_lists_every_field_error_for_validation() {
+        let resp = ApiError::Validation(vec![
+            FieldError::new("username", "is too short"),
+            FieldError::new("email", "must be a valid email address"),
+        ])
+        .reply()
+        .into_response();
+        let status = resp.status();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+
+        let expected = json!({
+            "error": {
+                "code": "validation_failed",
+                "message": "Request validation failed",
+                "field": "username",
+                "details": [
+                    { "field": "username", "message": "is too short" },
+                    {
+                        "field": "email",
+                        "message": "must be a valid email address"
+                    }
+                ]
+            }
+        });
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body_json(&body), expected);
+    }
+
+    #[tokio::test]
+    async fn handle_rejection_maps_unknown_path_to_404() {
+        let filter = path!("known")
+            .and(get())
+            .map(warp::reply)
+            .recover(handle_rejection);
+        let resp = warp::test::request().path("/unknown").reply(&filter).await;
+
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_json(resp.body())["error"]["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn handle_rejection_maps_wrong_method_to_405() {
+        let filter = path!("known")
+            .and(get())
+            .map(warp::reply)
+            .recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/known")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            body_json(resp.body())["error"]["code"],
+            "method_not_allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_rejection_maps_bad_json_to_400() {
+        let filter = path!("known")
+            .and(warp::body::json())
+            .map(|body: Value| warp::reply::json(&body))
+            .recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/known")
+            .body("{not json")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_json(resp.body())["error"]["code"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn handle_rejection_adds_bearer_challenge_for_unauthorized() {
+        let filter = warp::any()
+            .and_then(|| async {
+                Err::<Json, Rejection>(warp::reject::custom(
+                    ApiError::Unauthorized,
+                ))
+            })
+            .recover(handle_rejection);
+        let resp = warp::test::request().reply(&filter).await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers()["www-authenticate"], "Bearer");
+    }
+
+    #[tokio::test]
+    async fn handle_rejection_adds_retry_after_for_service_unavailable() {
+        let filter = warp::any()
+            .and_then(|| async {
+                Err::<Json, Rejection>(warp::reject::custom(
+                    ApiError::ServiceUnavailable,
+                ))
+            })
+            .recover(handle_rejection);
+        let resp = warp::test::request().reply(&filter).await;
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            resp.headers()["retry-after"],
+            RETRY_AFTER_SECONDS.to_string()
+        );
+        assert_eq!(
+            body_json(resp.body())["error"]["code"],
+            "service_unavailable"
+        );
+    }
+}
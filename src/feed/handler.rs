@@ -0,0 +1,123 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::PgConnection;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{get, path, Filter};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::post::handler::IndexQuery;
+use crate::post::model::ListParams;
+use crate::post::view;
+use crate::reaction::repository::ReactionRepo;
+use crate::session::handler::with_auth;
+use crate::user::model::User;
+use crate::ConnectionPool;
+
+use super::strategy::Feed;
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+    feed: Feed,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    path!("feed")
+        .and(get())
+        .and(warp::query::<IndexQuery>())
+        .and(with_auth(pool.clone(), settings))
+        .and(with_db_conn(pool))
+        .and(with_feed(feed))
+        .and_then(feed_index)
+}
+
+/// Hands the configured strategy to handlers that read or update timelines.
+pub fn with_feed(
+    feed: Feed,
+) -> impl Filter<Extract = (Feed,), Error = Infallible> + Clone {
+    warp::any().map(move || feed.clone())
+}
+
+async fn feed_index(
+    query: IndexQuery,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    feed: Feed,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        let (posts, next_cursor) =
+            feed.page(&conn, current_user.id, &params)?;
+        Ok::<_, diesel::result::Error>((
+            ReactionRepo::with_counts(&conn, posts)?,
+            next_cursor,
+        ))
+    })
+    .await;
+
+    match result {
+        Ok((posts, next_cursor)) => {
+            let resp = view::post_page(&posts, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::Reply;
+
+    use crate::error::handle_rejection;
+    use crate::feed::strategy::StrategyKind;
+    use crate::post::model::NewPost;
+    use crate::post::repository::PostRepo;
+    use crate::test_helpers::{
+        create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn feed_index_includes_own_posts() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let feed = StrategyKind::Write.build();
+        let new_post = NewPost {
+            author_id: bob.id,
+            body: "hello".to_string(),
+        };
+        let post = PostRepo::create(&conn, new_post).unwrap();
+        feed.on_post_created(&conn, &post).unwrap();
+        drop(conn);
+
+        let (parts, body) =
+            feed_index(IndexQuery::default(), bob, pool.get().unwrap(), feed)
+                .await
+                .unwrap()
+                .into_response()
+                .into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+
+        assert_eq!(parts.status, StatusCode::OK);
+        assert_eq!(
+            body,
+            view::post_page(&[(post, Default::default())], None).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn feed_route_requires_bearer_token() {
+        let filter = routes(
+            establish_connection(),
+            session_settings(),
+            StrategyKind::Read.build(),
+        )
+        .recover(handle_rejection);
+        let resp = warp::test::request().path("/feed").reply(&filter).await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
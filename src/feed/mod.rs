@@ -0,0 +1,4 @@
+pub mod handler;
+mod read;
+pub mod strategy;
+pub mod write;
@@ -0,0 +1,50 @@
+use diesel::prelude::*;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::{into_page, Cursor};
+use crate::post::model::{ListParams, Post};
+use crate::schema::{follows, posts, users};
+use crate::user::repository::active;
+
+use super::strategy::FeedStrategy;
+
+/// Assembles the feed from `posts` and `follows` at request time: writes are
+/// free, reads cost a join over everyone the user follows.
+pub struct FanOutOnRead;
+
+impl FeedStrategy for FanOutOnRead {
+    fn page(
+        &self,
+        conn: &PgConnection,
+        user_id: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<Post>, Option<Cursor>)> {
+        let followees = follows::table
+            .filter(follows::follower_id.eq(user_id))
+            .select(follows::followee_id);
+
+        let mut query = posts::table
+            .inner_join(users::table)
+            .filter(active())
+            .filter(
+                posts::author_id
+                    .eq(user_id)
+                    .or(posts::author_id.eq_any(followees)),
+            )
+            .select(posts::all_columns)
+            .order((posts::created_at.desc(), posts::id.desc()))
+            .into_boxed();
+
+        if let Some(cursor) = params.cursor {
+            query = query.filter(posts::created_at.lt(cursor.at).or(
+                posts::created_at.eq(cursor.at).and(posts::id.lt(cursor.id)),
+            ));
+        }
+
+        let posts = query.limit(params.limit + 1).load::<Post>(conn)?;
+        Ok(into_page(posts, params.limit, |post| {
+            Cursor::new(post.created_at, post.id)
+        }))
+    }
+}
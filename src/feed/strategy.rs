@@ -0,0 +1,259 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use diesel::{PgConnection, QueryResult};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+use crate::post::model::{ListParams, Post};
+
+use super::read::FanOutOnRead;
+use super::write::FanOutOnWrite;
+
+/// How home timelines are assembled. Write hooks run in the same
+/// transaction as the change that triggered them; strategies that read
+/// straight from `posts` and `follows` leave them as no-ops.
+pub trait FeedStrategy: Send + Sync {
+    /// Newest first posts by `user_id` and the accounts they follow; the
+    /// second value is the cursor for the following page, if any.
+    fn page(
+        &self,
+        conn: &PgConnection,
+        user_id: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<Post>, Option<Cursor>)>;
+
+    fn on_post_created(
+        &self,
+        _conn: &PgConnection,
+        _post: &Post,
+    ) -> QueryResult<()> {
+        Ok(())
+    }
+
+    fn on_follow(
+        &self,
+        _conn: &PgConnection,
+        _follower: Uuid,
+        _followee: Uuid,
+    ) -> QueryResult<()> {
+        Ok(())
+    }
+
+    fn on_unfollow(
+        &self,
+        _conn: &PgConnection,
+        _follower: Uuid,
+        _followee: Uuid,
+    ) -> QueryResult<()> {
+        Ok(())
+    }
+}
+
+pub type Feed = Arc<dyn FeedStrategy>;
+
+#[derive(Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StrategyKind {
+    /// Join `posts` against `follows` on every request.
+    Read,
+    /// Copy each post into its followers' `timeline_entries` when written.
+    Write,
+}
+
+impl StrategyKind {
+    pub fn build(self) -> Feed {
+        match self {
+            StrategyKind::Read => Arc::new(FanOutOnRead),
+            StrategyKind::Write => Arc::new(FanOutOnWrite),
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "read" => Ok(StrategyKind::Read),
+            "write" => Ok(StrategyKind::Write),
+            other => Err(format!("expected read or write, got {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrategyKind::Read => write!(f, "read"),
+            StrategyKind::Write => write!(f, "write"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::follow::model::NewFollow;
+    use crate::follow::repository::FollowRepo;
+    use crate::post::model::NewPost;
+    use crate::post::repository::PostRepo;
+    use crate::test_helpers::{create_user, establish_connection};
+    use crate::user::model::User;
+    use crate::user::repository::UserRepo;
+
+    use super::*;
+
+    const STRATEGIES: [StrategyKind; 2] =
+        [StrategyKind::Read, StrategyKind::Write];
+
+    fn post(conn: &PgConnection, author: &User, feeds: &[Feed]) -> Post {
+        let new_post = NewPost {
+            author_id: author.id,
+            body: "hello".to_string(),
+        };
+        let post = PostRepo::create(conn, new_post).unwrap();
+        for feed in feeds {
+            feed.on_post_created(conn, &post).unwrap();
+        }
+        post
+    }
+
+    fn follow(
+        conn: &PgConnection,
+        follower: &User,
+        followee: &User,
+        feeds: &[Feed],
+    ) {
+        let new_follow = NewFollow {
+            follower_id: follower.id,
+            followee_id: followee.id,
+        };
+        FollowRepo::follow(conn, new_follow).unwrap();
+        for feed in feeds {
+            feed.on_follow(conn, follower.id, followee.id).unwrap();
+        }
+    }
+
+    fn unfollow(
+        conn: &PgConnection,
+        follower: &User,
+        followee: &User,
+        feeds: &[Feed],
+    ) {
+        FollowRepo::unfollow(conn, follower.id, followee.id).unwrap();
+        for feed in feeds {
+            feed.on_unfollow(conn, follower.id, followee.id).unwrap();
+        }
+    }
+
+    /// Walks every page of `user`'s feed.
+    fn read_all(conn: &PgConnection, feed: &Feed, user: &User) -> Vec<Post> {
+        let mut posts = Vec::new();
+        let mut cursor = None;
+        loop {
+            let params = ListParams { limit: 2, cursor };
+            let (page, next) = feed.page(conn, user.id, &params).unwrap();
+            posts.extend(page);
+            match next {
+                Some(next) => cursor = Some(next),
+                None => return posts,
+            }
+        }
+    }
+
+    #[test]
+    fn parses_strategy_kind() {
+        assert_eq!("read".parse(), Ok(StrategyKind::Read));
+        assert_eq!("write".parse(), Ok(StrategyKind::Write));
+        assert!("push".parse::<StrategyKind>().is_err());
+    }
+
+    #[test]
+    fn strategies_return_identical_feeds() {
+        let conn = establish_connection().get().unwrap();
+        let feeds: Vec<Feed> =
+            STRATEGIES.iter().map(|kind| kind.build()).collect();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let carol = create_user(&conn);
+        let dave = create_user(&conn);
+
+        post(&conn, &alice, &feeds);
+        follow(&conn, &bob, &alice, &feeds);
+        follow(&conn, &bob, &carol, &feeds);
+        for author in &[&bob, &alice, &carol, &dave, &alice] {
+            post(&conn, author, &feeds);
+        }
+        unfollow(&conn, &bob, &carol, &feeds);
+        follow(&conn, &carol, &bob, &feeds);
+
+        for user in &[&bob, &carol, &dave] {
+            let read = read_all(&conn, &feeds[0], user);
+            let write = read_all(&conn, &feeds[1], user);
+            assert_eq!(read, write);
+        }
+
+        let bobs_feed = read_all(&conn, &feeds[0], &bob);
+        assert_eq!(bobs_feed.len(), 4);
+        assert!(
+            bobs_feed
+                .iter()
+                .all(|post| post.author_id == bob.id
+                    || post.author_id == alice.id)
+        );
+        assert!(bobs_feed.windows(2).all(|pair| (
+            pair[0].created_at,
+            pair[0].id
+        ) > (
+            pair[1].created_at,
+            pair[1].id
+        )));
+    }
+
+    #[test]
+    fn feeds_skip_posts_by_inactive_authors() {
+        let conn = establish_connection().get().unwrap();
+        let feeds: Vec<Feed> =
+            STRATEGIES.iter().map(|kind| kind.build()).collect();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        follow(&conn, &bob, &alice, &feeds);
+        follow(&conn, &bob, &eve, &feeds);
+        let kept = post(&conn, &alice, &feeds);
+        post(&conn, &eve, &feeds);
+        UserRepo::schedule_deletion(&conn, eve.id).unwrap();
+
+        for feed in &feeds {
+            assert_eq!(read_all(&conn, feed, &bob), vec![kept.clone()]);
+        }
+    }
+
+    #[test]
+    fn feeds_resume_after_cursor_post_is_deleted() {
+        let conn = establish_connection().get().unwrap();
+        let feeds: Vec<Feed> =
+            STRATEGIES.iter().map(|kind| kind.build()).collect();
+        for feed in &feeds {
+            let bob = create_user(&conn);
+            for _ in 0..3 {
+                post(&conn, &bob, &feeds);
+            }
+
+            let params = ListParams {
+                limit: 1,
+                cursor: None,
+            };
+            let (first, cursor) = feed.page(&conn, bob.id, &params).unwrap();
+            PostRepo::delete(&conn, first[0].id).unwrap();
+            let params = ListParams { limit: 1, cursor };
+            let (second, cursor) = feed.page(&conn, bob.id, &params).unwrap();
+
+            assert_eq!(second.len(), 1);
+            assert!(cursor.is_some());
+            assert_ne!(second[0].id, first[0].id);
+        }
+    }
+}
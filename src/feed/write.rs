@@ -0,0 +1,132 @@
+use diesel::prelude::*;
+use diesel::sql_types;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::{into_page, Cursor};
+use crate::post::model::{ListParams, Post};
+use crate::schema::{posts, timeline_entries, users};
+use crate::user::repository::active;
+
+use super::strategy::FeedStrategy;
+
+/// Copies every post into its author's and followers' `timeline_entries`
+/// as it is written, so a feed read is a single index range scan.
+/// Entries are only maintained while this strategy is active; run
+/// `social-net rebuild-feed` after switching to it.
+pub struct FanOutOnWrite;
+
+impl FanOutOnWrite {
+    /// Recomputes every timeline from `posts` and `follows`.
+    pub fn rebuild(conn: &PgConnection) -> QueryResult<usize> {
+        conn.transaction(|| {
+            diesel::delete(timeline_entries::table).execute(conn)?;
+            diesel::sql_query(
+                "INSERT INTO timeline_entries \
+                     (user_id, post_id, author_id, created_at) \
+                 SELECT posts.author_id, posts.id, posts.author_id, \
+                     posts.created_at \
+                 FROM posts \
+                 UNION ALL \
+                 SELECT follows.follower_id, posts.id, posts.author_id, \
+                     posts.created_at \
+                 FROM follows \
+                 JOIN posts ON posts.author_id = follows.followee_id \
+                 ON CONFLICT DO NOTHING",
+            )
+            .execute(conn)
+        })
+    }
+}
+
+impl FeedStrategy for FanOutOnWrite {
+    fn page(
+        &self,
+        conn: &PgConnection,
+        user_id: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<Post>, Option<Cursor>)> {
+        let mut query = timeline_entries::table
+            .inner_join(posts::table.inner_join(users::table))
+            .filter(timeline_entries::user_id.eq(user_id))
+            .filter(active())
+            .select(posts::all_columns)
+            .order((
+                timeline_entries::created_at.desc(),
+                timeline_entries::post_id.desc(),
+            ))
+            .into_boxed();
+
+        if let Some(cursor) = params.cursor {
+            query = query.filter(
+                timeline_entries::created_at.lt(cursor.at).or(
+                    timeline_entries::created_at
+                        .eq(cursor.at)
+                        .and(timeline_entries::post_id.lt(cursor.id)),
+                ),
+            );
+        }
+
+        // Entries copy their post's `created_at`, so the post's own position
+        // is also its position in the timeline.
+        let posts = query.limit(params.limit + 1).load::<Post>(conn)?;
+        Ok(into_page(posts, params.limit, |post| {
+            Cursor::new(post.created_at, post.id)
+        }))
+    }
+
+    fn on_post_created(
+        &self,
+        conn: &PgConnection,
+        post: &Post,
+    ) -> QueryResult<()> {
+        diesel::sql_query(
+            "INSERT INTO timeline_entries \
+                 (user_id, post_id, author_id, created_at) \
+             SELECT $1, $2, $1, $3 \
+             UNION ALL \
+             SELECT follower_id, $2, $1, $3 FROM follows \
+             WHERE followee_id = $1 \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind::<sql_types::Uuid, _>(post.author_id)
+        .bind::<sql_types::Uuid, _>(post.id)
+        .bind::<sql_types::Timestamp, _>(post.created_at)
+        .execute(conn)
+        .map(|_| ())
+    }
+
+    fn on_follow(
+        &self,
+        conn: &PgConnection,
+        follower: Uuid,
+        followee: Uuid,
+    ) -> QueryResult<()> {
+        diesel::sql_query(
+            "INSERT INTO timeline_entries \
+                 (user_id, post_id, author_id, created_at) \
+             SELECT $1, id, author_id, created_at FROM posts \
+             WHERE author_id = $2 \
+             ON CONFLICT DO NOTHING",
+        )
+        .bind::<sql_types::Uuid, _>(follower)
+        .bind::<sql_types::Uuid, _>(followee)
+        .execute(conn)
+        .map(|_| ())
+    }
+
+    fn on_unfollow(
+        &self,
+        conn: &PgConnection,
+        follower: Uuid,
+        followee: Uuid,
+    ) -> QueryResult<()> {
+        diesel::delete(
+            timeline_entries::table
+                .filter(timeline_entries::user_id.eq(follower))
+                .filter(timeline_entries::author_id.eq(followee)),
+        )
+        .execute(conn)
+        .map(|_| ())
+    }
+}
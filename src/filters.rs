@@ -0,0 +1,23 @@
+use std::convert::Infallible;
+
+use serde::de::DeserializeOwned;
+use warp::{Filter, Rejection};
+
+use crate::config::{AccountSettings, SessionSettings};
+
+pub fn json_body<T: DeserializeOwned + Send>(
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::body::json()
+}
+
+pub fn with_settings(
+    settings: SessionSettings,
+) -> impl Filter<Extract = (SessionSettings,), Error = Infallible> + Clone {
+    warp::any().map(move || settings.clone())
+}
+
+pub fn with_account(
+    account: AccountSettings,
+) -> impl Filter<Extract = (AccountSettings,), Error = Infallible> + Clone {
+    warp::any().map(move || account.clone())
+}
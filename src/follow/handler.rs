@@ -0,0 +1,280 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{Connection, PgConnection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{delete, get, path, put, Filter};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::error::ApiError;
+use crate::feed::handler::with_feed;
+use crate::feed::strategy::Feed;
+use crate::pagination::Cursor;
+use crate::session::handler::with_auth;
+use crate::user::model::User;
+use crate::user::repository::UserRepo;
+use crate::ConnectionPool;
+
+use super::model::{ListParams, NewFollow};
+use super::repository::{Direction, FollowRepo};
+use super::view;
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+    feed: Feed,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let follow_route = path!("users" / Uuid / "follow")
+        .and(put())
+        .and(with_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(with_feed(feed.clone()))
+        .and_then(follow);
+
+    let unfollow_route = path!("users" / Uuid / "follow")
+        .and(delete())
+        .and(with_auth(pool.clone(), settings))
+        .and(with_db_conn(pool.clone()))
+        .and(with_feed(feed))
+        .and_then(unfollow);
+
+    let followers_route = path!("users" / Uuid / "followers")
+        .and(get())
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool.clone()))
+        .and_then(|id, query, conn| {
+            follow_index(id, Direction::Followers, query, conn)
+        });
+
+    let following_route = path!("users" / Uuid / "following")
+        .and(get())
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool))
+        .and_then(|id, query, conn| {
+            follow_index(id, Direction::Following, query, conn)
+        });
+
+    follow_route
+        .or(unfollow_route)
+        .or(followers_route)
+        .or(following_route)
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<Cursor>,
+}
+
+async fn follow(
+    id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    feed: Feed,
+) -> Result<WithStatus<Json>, Infallible> {
+    if current_user.id == id {
+        return Ok(
+            ApiError::BadRequest("You can't follow yourself".into()).reply()
+        );
+    }
+
+    let result = blocking(move || {
+        UserRepo::find(&conn, id)?;
+        let new_follow = NewFollow {
+            follower_id: current_user.id,
+            followee_id: id,
+        };
+        conn.transaction(|| {
+            FollowRepo::follow(&conn, new_follow)?;
+            feed.on_follow(&conn, current_user.id, id)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(()) => {
+            let resp = view::follow_status(id, true);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn unfollow(
+    id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    feed: Feed,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        UserRepo::find(&conn, id)?;
+        conn.transaction(|| {
+            FollowRepo::unfollow(&conn, current_user.id, id)?;
+            feed.on_unfollow(&conn, current_user.id, id)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(_) => {
+            let resp = view::follow_status(id, false);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn follow_index(
+    id: Uuid,
+    direction: Direction,
+    query: IndexQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        UserRepo::find(&conn, id)?;
+        FollowRepo::list(&conn, id, direction, &params)
+    })
+    .await;
+
+    match result {
+        Ok((entries, next_cursor)) => {
+            let resp = view::follow_page(&entries, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use warp::Reply;
+
+    use crate::error::handle_rejection;
+    use crate::feed::strategy::StrategyKind;
+    use crate::follow::model::FollowCounts;
+    use crate::test_helpers::{
+        create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    fn feed() -> Feed {
+        StrategyKind::Write.build()
+    }
+
+    async fn body_json(resp: WithStatus<Json>) -> (StatusCode, Value) {
+        let (parts, body) = resp.into_response().into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (parts.status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn follow_then_unfollow_updates_counts() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        drop(conn);
+
+        let resp = follow(alice.id, bob.clone(), pool.get().unwrap(), feed())
+            .await
+            .unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, view::follow_status(alice.id, true));
+        assert_eq!(
+            FollowRepo::counts(&pool.get().unwrap(), alice.id).unwrap(),
+            FollowCounts {
+                followers: 1,
+                following: 0
+            }
+        );
+
+        let resp = unfollow(alice.id, bob, pool.get().unwrap(), feed())
+            .await
+            .unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, view::follow_status(alice.id, false));
+        assert_eq!(
+            FollowRepo::counts(&pool.get().unwrap(), alice.id).unwrap(),
+            FollowCounts::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn follow_rejects_self_and_unknown_users() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+
+        let resp = follow(bob.id, bob.clone(), pool.get().unwrap(), feed())
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let resp = follow(Uuid::new_v4(), bob, pool.get().unwrap(), feed())
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn follow_index_lists_followers_and_following() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let new_follow = NewFollow {
+            follower_id: alice.id,
+            followee_id: bob.id,
+        };
+        FollowRepo::follow(&conn, new_follow).unwrap();
+        drop(conn);
+
+        let resp = follow_index(
+            bob.id,
+            Direction::Followers,
+            IndexQuery::default(),
+            pool.get().unwrap(),
+        )
+        .await
+        .unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["id"], alice.id.to_string());
+
+        let resp = follow_index(
+            bob.id,
+            Direction::Following,
+            IndexQuery::default(),
+            pool.get().unwrap(),
+        )
+        .await
+        .unwrap();
+        let (_, body) = body_json(resp).await;
+        assert_eq!(body["data"], Value::Array(vec![]));
+    }
+
+    #[tokio::test]
+    async fn follow_route_requires_bearer_token() {
+        let filter = routes(
+            establish_connection(),
+            session_settings(),
+            StrategyKind::Read.build(),
+        )
+        .recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("PUT")
+            .path(&format!("/users/{}/follow", Uuid::new_v4()))
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+use crate::schema::follows;
+use crate::user::model::{User, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use super::handler::IndexQuery;
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct Follow {
+    pub follower_id: Uuid,
+    pub followee_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "follows"]
+pub struct NewFollow {
+    pub follower_id: Uuid,
+    pub followee_id: Uuid,
+}
+
+/// A listed user and when the follow started.
+pub type FollowEntry = (User, NaiveDateTime);
+
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+pub struct FollowCounts {
+    pub followers: i64,
+    pub following: i64,
+}
+
+/// Pages run most recent follow first; `cursor` is the position of the last
+/// follow already seen.
+#[derive(PartialEq, Debug)]
+pub struct ListParams {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+}
+
+impl From<IndexQuery> for ListParams {
+    fn from(query: IndexQuery) -> Self {
+        ListParams {
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            cursor: query.cursor,
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::{into_page, Cursor};
+use crate::schema::{follows, users};
+use crate::user::model::User;
+use crate::user::repository::{active, active_ids};
+
+use super::model::{Follow, FollowCounts, FollowEntry, ListParams, NewFollow};
+
+/// Which side of the follow graph to list for a user.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Direction {
+    Followers,
+    Following,
+}
+
+pub struct FollowRepo;
+
+impl FollowRepo {
+    /// Idempotent: following someone twice keeps the original timestamp.
+    pub fn follow(
+        conn: &PgConnection,
+        new_follow: NewFollow,
+    ) -> QueryResult<()> {
+        diesel::insert_into(follows::table)
+            .values(new_follow)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .map(|_| ())
+    }
+
+    pub fn unfollow(
+        conn: &PgConnection,
+        follower: Uuid,
+        followee: Uuid,
+    ) -> QueryResult<usize> {
+        diesel::delete(follows::table.find((follower, followee))).execute(conn)
+    }
+
+    pub fn is_following(
+        conn: &PgConnection,
+        follower: Uuid,
+        followee: Uuid,
+    ) -> QueryResult<bool> {
+        diesel::select(exists(follows::table.find((follower, followee))))
+            .get_result(conn)
+    }
+
+    /// Follows from and to deactivated or deleted accounts aren't counted.
+    pub fn counts(
+        conn: &PgConnection,
+        user_id: Uuid,
+    ) -> QueryResult<FollowCounts> {
+        let followers = follows::table
+            .filter(follows::followee_id.eq(user_id))
+            .filter(follows::follower_id.eq_any(active_ids()))
+            .count()
+            .get_result(conn)?;
+        let following = follows::table
+            .filter(follows::follower_id.eq(user_id))
+            .filter(follows::followee_id.eq_any(active_ids()))
+            .count()
+            .get_result(conn)?;
+        Ok(FollowCounts {
+            followers,
+            following,
+        })
+    }
+
+    /// Active users on one side of `user_id`'s follow graph with the time
+    /// each follow started, newest first. The second value is the cursor for
+    /// the following page, if any.
+    pub fn list(
+        conn: &PgConnection,
+        user_id: Uuid,
+        direction: Direction,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<FollowEntry>, Option<Cursor>)> {
+        let query = follows::table.into_boxed();
+        let query = match direction {
+            Direction::Followers => {
+                let query = query
+                    .filter(follows::followee_id.eq(user_id))
+                    .filter(follows::follower_id.eq_any(active_ids()))
+                    .order((
+                        follows::created_at.desc(),
+                        follows::follower_id.desc(),
+                    ));
+                match params.cursor {
+                    Some(cursor) => query.filter(
+                        follows::created_at.lt(cursor.at).or(
+                            follows::created_at
+                                .eq(cursor.at)
+                                .and(follows::follower_id.lt(cursor.id)),
+                        ),
+                    ),
+                    None => query,
+                }
+            }
+            Direction::Following => {
+                let query = query
+                    .filter(follows::follower_id.eq(user_id))
+                    .filter(follows::followee_id.eq_any(active_ids()))
+                    .order((
+                        follows::created_at.desc(),
+                        follows::followee_id.desc(),
+                    ));
+                match params.cursor {
+                    Some(cursor) => query.filter(
+                        follows::created_at.lt(cursor.at).or(
+                            follows::created_at
+                                .eq(cursor.at)
+                                .and(follows::followee_id.lt(cursor.id)),
+                        ),
+                    ),
+                    None => query,
+                }
+            }
+        };
+
+        let other = |follow: &Follow| match direction {
+            Direction::Followers => follow.follower_id,
+            Direction::Following => follow.followee_id,
+        };
+        let page = query.limit(params.limit + 1).load::<Follow>(conn)?;
+        let (page, next_cursor) = into_page(page, params.limit, |follow| {
+            Cursor::new(follow.created_at, other(follow))
+        });
+
+        let ids: Vec<Uuid> = page.iter().map(other).collect();
+        let mut found: HashMap<Uuid, User> = users::table
+            .filter(users::id.eq_any(&ids))
+            .filter(active())
+            .load::<User>(conn)?
+            .into_iter()
+            .map(|user| (user.id, user))
+            .collect();
+
+        let entries = page
+            .iter()
+            .filter_map(|follow| {
+                found
+                    .remove(&other(follow))
+                    .map(|user| (user, follow.created_at))
+            })
+            .collect();
+
+        Ok((entries, next_cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{create_user, establish_connection};
+    use crate::user::repository::UserRepo;
+
+    use super::*;
+
+    fn follow(conn: &PgConnection, follower: &User, followee: &User) {
+        let new_follow = NewFollow {
+            follower_id: follower.id,
+            followee_id: followee.id,
+        };
+        FollowRepo::follow(conn, new_follow).expect("Failed to follow");
+    }
+
+    #[test]
+    fn follow_is_idempotent_and_counted() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+
+        follow(&conn, &bob, &alice);
+        follow(&conn, &bob, &alice);
+
+        let expected = FollowCounts {
+            followers: 1,
+            following: 0,
+        };
+        assert_eq!(FollowRepo::counts(&conn, alice.id).unwrap(), expected);
+        assert_eq!(FollowRepo::counts(&conn, bob.id).unwrap().following, 1);
+    }
+
+    #[test]
+    fn unfollow_removes_relation() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        follow(&conn, &bob, &alice);
+
+        assert_eq!(FollowRepo::unfollow(&conn, bob.id, alice.id).unwrap(), 1);
+        assert_eq!(FollowRepo::unfollow(&conn, bob.id, alice.id).unwrap(), 0);
+        assert_eq!(
+            FollowRepo::counts(&conn, alice.id).unwrap(),
+            FollowCounts::default()
+        );
+    }
+
+    #[test]
+    fn self_follow_is_rejected_by_database() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let new_follow = NewFollow {
+            follower_id: bob.id,
+            followee_id: bob.id,
+        };
+
+        assert!(FollowRepo::follow(&conn, new_follow).is_err());
+    }
+
+    #[test]
+    fn list_pages_through_both_directions() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let fans: Vec<User> = (0..3).map(|_| create_user(&conn)).collect();
+        for fan in &fans {
+            follow(&conn, fan, &bob);
+        }
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+        };
+        let (first, cursor) =
+            FollowRepo::list(&conn, bob.id, Direction::Followers, &params)
+                .unwrap();
+        let params = ListParams { limit: 2, cursor };
+        let (second, cursor) =
+            FollowRepo::list(&conn, bob.id, Direction::Followers, &params)
+                .unwrap();
+
+        let mut listed: Vec<Uuid> = first
+            .iter()
+            .chain(&second)
+            .map(|(user, _)| user.id)
+            .collect();
+        let mut expected: Vec<Uuid> = fans.iter().map(|fan| fan.id).collect();
+        listed.sort();
+        expected.sort();
+        assert_eq!(listed, expected);
+        assert_eq!(cursor, None);
+
+        let params = ListParams {
+            limit: 10,
+            cursor: None,
+        };
+        let (following, _) =
+            FollowRepo::list(&conn, fans[0].id, Direction::Following, &params)
+                .unwrap();
+        assert_eq!(following.len(), 1);
+        assert_eq!(following[0].0, bob);
+    }
+
+    #[test]
+    fn list_resumes_after_cursor_follow_is_removed() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let fans: Vec<User> = (0..3).map(|_| create_user(&conn)).collect();
+        for fan in &fans {
+            follow(&conn, fan, &bob);
+        }
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+        };
+        let (first, cursor) =
+            FollowRepo::list(&conn, bob.id, Direction::Followers, &params)
+                .unwrap();
+        FollowRepo::unfollow(&conn, first[1].0.id, bob.id).unwrap();
+        let params = ListParams { limit: 2, cursor };
+        let (second, cursor) =
+            FollowRepo::list(&conn, bob.id, Direction::Followers, &params)
+                .unwrap();
+
+        assert_eq!(second.len(), 1);
+        assert_eq!(cursor, None);
+        assert!(first.iter().all(|(user, _)| user.id != second[0].0.id));
+    }
+
+    #[test]
+    fn counts_and_lists_skip_inactive_users() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let eve = create_user(&conn);
+        follow(&conn, &alice, &bob);
+        follow(&conn, &eve, &bob);
+        follow(&conn, &bob, &eve);
+        UserRepo::deactivate(&conn, eve.id).unwrap();
+
+        let expected = FollowCounts {
+            followers: 1,
+            following: 0,
+        };
+        assert_eq!(FollowRepo::counts(&conn, bob.id).unwrap(), expected);
+
+        let params = ListParams {
+            limit: 10,
+            cursor: None,
+        };
+        let (followers, _) =
+            FollowRepo::list(&conn, bob.id, Direction::Followers, &params)
+                .unwrap();
+        let (following, _) =
+            FollowRepo::list(&conn, bob.id, Direction::Following, &params)
+                .unwrap();
+        assert_eq!(followers.len(), 1);
+        assert_eq!(followers[0].0.id, alice.id);
+        assert!(following.is_empty());
+    }
+}
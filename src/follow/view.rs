@@ -0,0 +1,77 @@
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+
+use super::model::FollowEntry;
+
+pub fn follow_status(user_id: Uuid, following: bool) -> Value {
+    json!({
+        "user_id": user_id,
+        "following": following
+    })
+}
+
+pub fn follow_page(
+    entries: &[FollowEntry],
+    next_cursor: Option<Cursor>,
+) -> Value {
+    let data: Vec<Value> = entries
+        .iter()
+        .map(|(user, followed_at)| {
+            json!({
+                "id": user.id,
+                "username": user.username,
+                "followed_at": followed_at
+            })
+        })
+        .collect();
+
+    json!({
+        "data": data,
+        "next_cursor": next_cursor
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::user::model::User;
+
+    use super::*;
+
+    #[test]
+    fn follow_page_lists_users_with_follow_time() {
+        let bob = User {
+            id: Uuid::new_v4(),
+            username: "bob".to_string(),
+            email: "bob@open.org".to_string(),
+            password: "secret".to_string(),
+            display_name: None,
+            bio: None,
+            avatar_url: None,
+            location: None,
+            website: None,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            is_admin: false,
+            deactivated_at: None,
+            deleted_at: None,
+            email_verified_at: None,
+            password_hashed_at: None,
+        };
+        let followed_at = Utc::now().naive_utc();
+
+        let expected = json!({
+            "data": [{
+                "id": bob.id,
+                "username": "bob",
+                "followed_at": followed_at
+            }],
+            "next_cursor": null
+        });
+
+        assert_eq!(follow_page(&[(bob, followed_at)], None), expected);
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::{Email, MailError, MailSettings, Mailer};
+
+/// Writes every message to its own `.eml` file instead of sending it.
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(settings: &MailSettings) -> Self {
+        FileMailer {
+            dir: PathBuf::from(&settings.dir),
+            from: settings.from.clone(),
+        }
+    }
+
+    fn render(&self, email: &Email) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n\r\n{}\r\n",
+            self.from,
+            email.to,
+            email.subject,
+            Utc::now().to_rfc2822(),
+            email.body
+        )
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        let name = format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4()
+        );
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(self.dir.join(name), self.render(email)))
+            .map_err(|err| MailError(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::mailer::smtp::SmtpSecurity;
+
+    use super::*;
+
+    #[test]
+    fn send_writes_one_file_per_message() {
+        let dir = env::temp_dir().join(format!("mail-{}", Uuid::new_v4()));
+        let mailer = FileMailer::new(&MailSettings {
+            from: "no-reply@open.org".to_string(),
+            dir: dir.to_string_lossy().into_owned(),
+            smtp_host: String::new(),
+            smtp_port: 465,
+            smtp_security: SmtpSecurity::Tls,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+        });
+        let email = Email {
+            to: "bob@open.org".to_string(),
+            subject: "Hello".to_string(),
+            body: "Hi Bob".to_string(),
+        };
+
+        mailer.send(&email).unwrap();
+        mailer.send(&email).unwrap();
+
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 2);
+        let contents =
+            fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(contents.starts_with("From: no-reply@open.org\r\n"));
+        assert!(contents.contains("To: bob@open.org\r\n"));
+        assert!(contents.ends_with("\r\n\r\nHi Bob\r\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
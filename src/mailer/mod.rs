@@ -0,0 +1,98 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use warp::Filter;
+
+use crate::error::ApiError;
+
+use self::file::FileMailer;
+use self::smtp::{SmtpMailer, SmtpSecurity};
+
+pub mod file;
+#[cfg(test)]
+pub mod memory;
+pub mod smtp;
+
+/// A plain-text message to a single recipient; the sender address comes
+/// from the mailer's configuration.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct MailError(pub String);
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "couldn't send email: {}", self.0)
+    }
+}
+
+impl From<MailError> for ApiError {
+    fn from(err: MailError) -> Self {
+        error!("{}", err);
+        ApiError::Internal
+    }
+}
+
+/// Delivers outgoing email. Sending blocks, so callers run it inside
+/// `db::blocking` alongside their queries.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+pub type SharedMailer = Arc<dyn Mailer>;
+
+/// What the mailers need from the configuration.
+#[derive(PartialEq, Clone, Debug)]
+pub struct MailSettings {
+    pub from: String,
+    pub dir: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: String,
+    pub smtp_password: String,
+}
+
+#[derive(Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum MailerKind {
+    /// Write each message to a file under `mail_dir`, for development.
+    File,
+    /// Relay through `smtp_host`.
+    Smtp,
+}
+
+impl MailerKind {
+    pub fn build(self, settings: &MailSettings) -> SharedMailer {
+        match self {
+            MailerKind::File => Arc::new(FileMailer::new(settings)),
+            MailerKind::Smtp => Arc::new(SmtpMailer::new(settings)),
+        }
+    }
+}
+
+impl FromStr for MailerKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "file" => Ok(MailerKind::File),
+            "smtp" => Ok(MailerKind::Smtp),
+            other => Err(format!("expected file or smtp, got {}", other)),
+        }
+    }
+}
+
+pub fn with_mailer(
+    mailer: SharedMailer,
+) -> impl Filter<Extract = (SharedMailer,), Error = Infallible> + Clone {
+    warp::any().map(move || mailer.clone())
+}
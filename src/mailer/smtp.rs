@@ -0,0 +1,124 @@
+use std::fmt;
+use std::str::FromStr;
+
+use lettre::smtp::authentication::Credentials;
+use lettre::{ClientSecurity, ClientTlsParameters, SmtpClient, Transport};
+use lettre_email::EmailBuilder;
+use native_tls::TlsConnector;
+use serde::Deserialize;
+
+use super::{Email, MailError, MailSettings, Mailer};
+
+#[derive(Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Implicit TLS, usually on port 465.
+    Tls,
+    /// Upgrade a plain connection with STARTTLS, usually on port 587.
+    StartTls,
+    /// No encryption; only for local relays such as MailHog.
+    None,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "tls" => Ok(SmtpSecurity::Tls),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "none" => Ok(SmtpSecurity::None),
+            other => {
+                Err(format!("expected tls, starttls or none, got {}", other))
+            }
+        }
+    }
+}
+
+/// Opens a fresh connection per message, which is plenty for the handful
+/// of account emails the server sends.
+pub struct SmtpMailer {
+    from: String,
+    host: String,
+    port: u16,
+    security: SmtpSecurity,
+    credentials: Option<(String, String)>,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: &MailSettings) -> Self {
+        let credentials = if settings.smtp_username.is_empty() {
+            None
+        } else {
+            Some((
+                settings.smtp_username.clone(),
+                settings.smtp_password.clone(),
+            ))
+        };
+        SmtpMailer {
+            from: settings.from.clone(),
+            host: settings.smtp_host.clone(),
+            port: settings.smtp_port,
+            security: settings.smtp_security,
+            credentials,
+        }
+    }
+
+    fn client(&self) -> Result<SmtpClient, MailError> {
+        let security = match self.security {
+            SmtpSecurity::None => ClientSecurity::None,
+            SmtpSecurity::Tls | SmtpSecurity::StartTls => {
+                let connector = TlsConnector::new().map_err(to_mail_error)?;
+                let params =
+                    ClientTlsParameters::new(self.host.clone(), connector);
+                if self.security == SmtpSecurity::Tls {
+                    ClientSecurity::Wrapper(params)
+                } else {
+                    ClientSecurity::Required(params)
+                }
+            }
+        };
+        let client = SmtpClient::new((self.host.as_str(), self.port), security)
+            .map_err(to_mail_error)?;
+        Ok(match &self.credentials {
+            Some((username, password)) => client.credentials(Credentials::new(
+                username.clone(),
+                password.clone(),
+            )),
+            None => client,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: &Email) -> Result<(), MailError> {
+        let message = EmailBuilder::new()
+            .from(self.from.as_str())
+            .to(email.to.as_str())
+            .subject(email.subject.as_str())
+            .text(email.body.as_str())
+            .build()
+            .map_err(to_mail_error)?;
+        self.client()?
+            .transport()
+            .send(message.into())
+            .map(|_| ())
+            .map_err(to_mail_error)
+    }
+}
+
+fn to_mail_error(err: impl fmt::Display) -> MailError {
+    MailError(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smtp_security_parses_from_env_values() {
+        assert_eq!("starttls".parse(), Ok(SmtpSecurity::StartTls));
+        assert_eq!("none".parse(), Ok(SmtpSecurity::None));
+        assert!("ssl".parse::<SmtpSecurity>().is_err());
+    }
+}
@@ -5,27 +5,217 @@ extern crate dotenv;
 extern crate log;
 extern crate pretty_env_logger;
 
+use std::{env, process};
+
 use diesel::r2d2::{ConnectionManager, Pool};
 use diesel::PgConnection;
+use tokio::sync::oneshot;
 use warp::Filter;
 
+use crate::config::Config;
+use crate::feed::write::FanOutOnWrite;
+use crate::shutdown::{Shutdown, Tasks};
+use crate::user::purger;
+use crate::user::repository::UserRepo;
+
+mod comment;
+mod config;
+mod conversation;
+mod db;
 mod echo;
+mod error;
+mod feed;
+mod filters;
+mod follow;
+mod mailer;
+mod migrations;
+mod pagination;
+mod password_reset;
 mod ping;
+mod post;
+mod reaction;
 mod router;
 mod schema;
+mod session;
+mod shutdown;
 mod user;
+mod verification;
 
 type ConnectionPool = Pool<ConnectionManager<PgConnection>>;
 
+const HASH_BATCH_SIZE: i64 = 100;
+
 #[tokio::main]
 async fn main() {
-    pretty_env_logger::init();
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("Invalid configuration: {}", err);
+        process::exit(1);
+    });
+
+    pretty_env_logger::formatted_builder()
+        .parse_filters(&config.log_level)
+        .init();
+
+    let db_pool = router::establish_connection(&config).unwrap_or_else(|err| {
+        error!("Postgres connection pool couldn't be created: {}", err);
+        process::exit(1);
+    });
+
+    match env::args().nth(1).as_deref() {
+        None => serve(config, db_pool).await,
+        Some("migrate") => {
+            if let Err(err) = prepare_schema(&db_pool, true, false) {
+                error!("{}", err);
+                process::exit(1);
+            }
+        }
+        Some("rebuild-feed") => match rebuild_feed(&db_pool) {
+            Ok(count) => info!("Rebuilt feed with {} timeline entries", count),
+            Err(err) => {
+                error!("Couldn't rebuild feed: {}", err);
+                process::exit(1);
+            }
+        },
+        Some("hash-passwords") => match hash_passwords(&db_pool) {
+            Ok(count) => info!("Hashed {} legacy plaintext passwords", count),
+            Err(err) => {
+                error!("Couldn't hash legacy passwords: {}", err);
+                process::exit(1);
+            }
+        },
+        Some("purge-users") => {
+            match purger::purge(&db_pool, config.account().deletion_grace) {
+                Ok(count) => info!("Purged {} deleted accounts", count),
+                Err(err) => {
+                    error!("Couldn't purge deleted accounts: {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        Some(command) => {
+            eprintln!(
+                "Unknown command {}. Usage: social-net [migrate|rebuild-feed|hash-passwords|purge-users]",
+                command
+            );
+            process::exit(2);
+        }
+    }
+}
+
+/// Applies pending migrations when `apply` is set. Otherwise pending
+/// migrations are an error unless `allow_pending` is set, in which case
+/// they're only logged. Either way a schema newer than this binary is an
+/// error.
+fn prepare_schema(
+    pool: &ConnectionPool,
+    apply: bool,
+    allow_pending: bool,
+) -> Result<(), String> {
+    let conn = pool.get().map_err(|err| err.to_string())?;
+    if apply {
+        let applied = migrations::run(&conn).map_err(|err| err.to_string())?;
+        if applied.is_empty() {
+            info!("Database schema is up to date");
+        }
+    } else {
+        let pending =
+            migrations::check(&conn).map_err(|err| err.to_string())?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if !allow_pending {
+            return Err(format!(
+                "pending migrations {}; run `social-net migrate` or set \
+                 MIGRATE_ON_STARTUP=true",
+                pending.join(", ")
+            ));
+        }
+        warn!("Pending migrations: {}", pending.join(", "));
+    }
+    Ok(())
+}
+
+fn rebuild_feed(pool: &ConnectionPool) -> Result<usize, String> {
+    let conn = pool.get().map_err(|err| err.to_string())?;
+    FanOutOnWrite::rebuild(&conn).map_err(|err| err.to_string())
+}
+
+/// Hashes plaintext passwords left from before hashing was introduced, a
+/// batch at a time so concurrent logins aren't held up.
+fn hash_passwords(pool: &ConnectionPool) -> Result<usize, String> {
+    let conn = pool.get().map_err(|err| err.to_string())?;
+    let mut total = 0;
+    loop {
+        let hashed = UserRepo::hash_legacy_passwords(&conn, HASH_BATCH_SIZE)
+            .map_err(|err| err.to_string())?;
+        if hashed == 0 {
+            return Ok(total);
+        }
+        total += hashed;
+    }
+}
+
+async fn serve(config: Config, db_pool: ConnectionPool) {
+    if let Err(err) = prepare_schema(
+        &db_pool,
+        config.migrate_on_startup,
+        config.allow_pending_migrations,
+    ) {
+        error!("Refusing to start: {}", err);
+        process::exit(1);
+    }
+
+    tokio::spawn(purger::run(
+        db_pool.clone(),
+        config.account(),
+        config.purge_interval(),
+    ));
+
+    let shutdown = Shutdown::default();
+    let tasks = Tasks::default();
     let log = warp::log("social_net");
-    let router = router::routes().with(log);
+    let router =
+        router::routes(db_pool, &config, shutdown.clone(), tasks.clone())
+            .with(log);
+
+    let (stop_accepting, stopped) = oneshot::channel::<()>();
+    let (addr, server) = warp::serve(router).bind_with_graceful_shutdown(
+        config.socket_addr(),
+        async {
+            stopped.await.ok();
+        },
+    );
+    let server = tokio::spawn(server);
+
+    info!("Starting Server on {}", addr);
+
+    shutdown::signal_received().await;
+    shutdown.start_draining();
+    info!(
+        "Failing readiness, still accepting connections for {:?}",
+        config.shutdown_grace()
+    );
+    tokio::time::delay_for(config.shutdown_grace()).await;
 
-    info!("Starting Server");
+    info!(
+        "Draining in-flight requests for up to {:?}",
+        config.drain_timeout()
+    );
+    stop_accepting.send(()).ok();
 
-    warp::serve(router).run(([127, 0, 0, 1], 8080)).await;
+    let finished = async {
+        server.await.ok();
+        tasks.finished().await;
+    };
+    if shutdown::drain(finished, config.drain_timeout()).await {
+        info!("Server stopped");
+    } else {
+        warn!(
+            "Drain deadline passed, dropping remaining connections and {} \
+             background tasks",
+            tasks.running()
+        );
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use diesel::connection::SimpleConnection;
+use diesel::dsl::sql;
+use diesel::result::Error;
+use diesel::sql_types::Bool;
+use diesel::{sql_query, Connection, PgConnection, QueryResult, RunQueryDsl};
+use diesel_migrations::{setup_database, MigrationConnection};
+
+/// Arbitrary key for the advisory lock that stops two instances starting at
+/// once from applying the same migration twice.
+const LOCK_KEY: i64 = 0x736f_6369_616c;
+
+struct EmbeddedMigration {
+    name: &'static str,
+    up_sql: &'static str,
+}
+
+macro_rules! embed {
+    ($name:literal) => {
+        EmbeddedMigration {
+            name: $name,
+            up_sql: include_str!(concat!("../migrations/", $name, "/up.sql")),
+        }
+    };
+}
+
+/// Migrations compiled into this binary, oldest first.
+const MIGRATIONS: &[EmbeddedMigration] = &[
+    embed!("00000000000000_diesel_initial_setup"),
+    embed!("2020-05-31-085059_create_users"),
+    embed!("2020-06-07-101530_create_sessions"),
+    embed!("2020-06-14-093012_case_insensitive_user_identity"),
+    embed!("2020-06-21-104512_create_posts"),
+    embed!("2020-06-28-091204_create_follows"),
+    embed!("2020-07-05-143020_create_timeline_entries"),
+    embed!("2020-07-12-180455_create_reactions"),
+    embed!("2020-07-19-120733_create_comments"),
+    embed!("2020-07-26-151022_create_conversations"),
+    embed!("2020-08-02-094417_add_user_profile_fields"),
+    embed!("2020-08-09-110254_add_user_admin_flag"),
+    embed!("2020-08-16-131508_add_user_soft_delete"),
+    embed!("2020-08-23-102236_create_email_verifications"),
+    embed!("2020-08-30-141907_create_password_resets"),
+    embed!("2020-09-06-101532_keep_comments_of_deleted_users"),
+    embed!("2020-09-13-093417_add_user_password_hashed_at"),
+    embed!("2020-09-13-104409_index_users_by_created_at"),
+];
+
+impl EmbeddedMigration {
+    /// Diesel records a migration under its directory prefix with dashes
+    /// removed.
+    fn version(&self) -> String {
+        version(self.name)
+    }
+}
+
+fn version(name: &str) -> String {
+    name.split('_').next().unwrap_or(name).replace('-', "")
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database has versions this binary doesn't know about, so it was
+    /// migrated by a newer release.
+    SchemaAhead(Vec<String>),
+    Failed(&'static str, Error),
+    Database(Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::SchemaAhead(versions) => write!(
+                f,
+                "database schema is ahead of this binary (unknown versions: {})",
+                versions.join(", ")
+            ),
+            MigrationError::Failed(name, err) => {
+                write!(f, "migration {} failed: {}", name, err)
+            }
+            MigrationError::Database(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<Error> for MigrationError {
+    fn from(err: Error) -> Self {
+        MigrationError::Database(err)
+    }
+}
+
+/// Versions shipped with this binary that haven't been applied yet.
+pub fn pending(conn: &PgConnection) -> QueryResult<Vec<String>> {
+    let applied = applied_versions(conn)?;
+    Ok(pending_in(MIGRATIONS, &applied)
+        .map(EmbeddedMigration::version)
+        .collect())
+}
+
+/// Fails if the database is ahead of this binary, otherwise returns the
+/// names of migrations still to apply. Only reads from the database.
+pub fn check(conn: &PgConnection) -> Result<Vec<&'static str>, MigrationError> {
+    let applied = applied_versions(conn)?;
+    ensure_known(MIGRATIONS, &applied)?;
+    Ok(pending_in(MIGRATIONS, &applied)
+        .map(|migration| migration.name)
+        .collect())
+}
+
+/// Applies every pending migration, each in its own transaction, and returns
+/// the names of those applied.
+pub fn run(conn: &PgConnection) -> Result<Vec<&'static str>, MigrationError> {
+    run_migrations(conn, MIGRATIONS)
+}
+
+fn run_migrations(
+    conn: &PgConnection,
+    migrations: &'static [EmbeddedMigration],
+) -> Result<Vec<&'static str>, MigrationError> {
+    setup_database(conn)?;
+    sql_query(format!("SELECT pg_advisory_lock({})", LOCK_KEY))
+        .execute(conn)?;
+    let result = apply_pending(conn, migrations);
+    sql_query(format!("SELECT pg_advisory_unlock({})", LOCK_KEY))
+        .execute(conn)?;
+    result
+}
+
+fn apply_pending(
+    conn: &PgConnection,
+    migrations: &'static [EmbeddedMigration],
+) -> Result<Vec<&'static str>, MigrationError> {
+    let applied = conn.previously_run_migration_versions()?;
+    ensure_known(migrations, &applied)?;
+
+    let mut names = Vec::new();
+    for migration in pending_in(migrations, &applied) {
+        conn.transaction(|| {
+            conn.batch_execute(migration.up_sql)?;
+            conn.insert_new_migration(&migration.version())
+        })
+        .map_err(|err| MigrationError::Failed(migration.name, err))?;
+        info!("Applied migration {}", migration.name);
+        names.push(migration.name);
+    }
+    Ok(names)
+}
+
+/// Like `previously_run_migration_versions`, but a database that was never
+/// migrated has applied nothing rather than lacking the bookkeeping table.
+fn applied_versions(conn: &PgConnection) -> QueryResult<HashSet<String>> {
+    let tracked = diesel::select(sql::<Bool>(
+        "to_regclass('__diesel_schema_migrations') IS NOT NULL",
+    ))
+    .get_result(conn)?;
+    if tracked {
+        conn.previously_run_migration_versions()
+    } else {
+        Ok(HashSet::new())
+    }
+}
+
+fn pending_in<'a>(
+    migrations: &'a [EmbeddedMigration],
+    applied: &'a HashSet<String>,
+) -> impl Iterator<Item = &'a EmbeddedMigration> {
+    migrations
+        .iter()
+        .filter(move |migration| !applied.contains(&migration.version()))
+}
+
+fn ensure_known(
+    migrations: &[EmbeddedMigration],
+    applied: &HashSet<String>,
+) -> Result<(), MigrationError> {
+    let known: HashSet<String> =
+        migrations.iter().map(EmbeddedMigration::version).collect();
+    let mut unknown: Vec<String> =
+        applied.difference(&known).cloned().collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        unknown.sort();
+        Err(MigrationError::SchemaAhead(unknown))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::test_helpers::establish_connection;
+
+    use super::*;
+
+    const TEST_MIGRATIONS: &[EmbeddedMigration] = &[EmbeddedMigration {
+        name: "2999-01-01-000000_create_widgets",
+        up_sql: "CREATE TABLE widgets (id SERIAL PRIMARY KEY);",
+    }];
+
+    #[test]
+    fn version_strips_name_and_dashes() {
+        assert_eq!(version("2020-05-31-085059_create_users"), "20200531085059");
+        assert_eq!(
+            version("00000000000000_diesel_initial_setup"),
+            "00000000000000"
+        );
+    }
+
+    #[test]
+    fn embedded_list_matches_migrations_directory() {
+        let mut names: Vec<String> = fs::read_dir("migrations")
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|entry| entry.file_type().unwrap().is_dir())
+            .map(|entry| entry.file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+
+        let embedded: Vec<&str> =
+            MIGRATIONS.iter().map(|migration| migration.name).collect();
+        assert_eq!(names, embedded);
+    }
+
+    #[test]
+    fn pending_is_empty_for_migrated_database() {
+        let conn = establish_connection().get().unwrap();
+        assert_eq!(pending(&conn).unwrap(), Vec::<String>::new());
+        assert_eq!(run(&conn).unwrap(), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn run_applies_and_records_pending_migrations() {
+        let conn = establish_connection().get().unwrap();
+
+        let applied = run_migrations(&conn, TEST_MIGRATIONS).unwrap_err();
+        assert!(matches!(applied, MigrationError::SchemaAhead(_)));
+
+        conn.batch_execute("DELETE FROM __diesel_schema_migrations")
+            .unwrap();
+        let applied = run_migrations(&conn, TEST_MIGRATIONS).unwrap();
+        assert_eq!(applied, vec!["2999-01-01-000000_create_widgets"]);
+        assert!(conn.batch_execute("SELECT id FROM widgets").is_ok());
+        assert!(run_migrations(&conn, TEST_MIGRATIONS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn check_refuses_schema_ahead_of_binary() {
+        let conn = establish_connection().get().unwrap();
+        conn.batch_execute(
+            "INSERT INTO __diesel_schema_migrations (version) \
+             VALUES ('29990101000000')",
+        )
+        .unwrap();
+
+        match check(&conn) {
+            Err(MigrationError::SchemaAhead(versions)) => {
+                assert_eq!(versions, vec!["29990101000000"])
+            }
+            result => panic!("Expected SchemaAhead, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn check_treats_untracked_database_as_unmigrated() {
+        let conn = establish_connection().get().unwrap();
+        conn.batch_execute("DROP TABLE __diesel_schema_migrations")
+            .unwrap();
+
+        assert_eq!(check(&conn).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(pending(&conn).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(applied_versions(&conn), Ok(HashSet::new()));
+    }
+
+    /// Rewinds `users` to before the case-insensitive migration, seeds
+    /// `usernames` and reruns it.
+    fn rerun_case_insensitive_identity(
+        conn: &PgConnection,
+        usernames: &[&str],
+    ) -> QueryResult<()> {
+        conn.batch_execute(
+            "DROP INDEX users_username_key; DROP INDEX users_email_key;",
+        )?;
+        for (i, username) in usernames.iter().enumerate() {
+            sql_query(
+                "INSERT INTO users (username, email, password) \
+                 VALUES ($1, $2, 'password')",
+            )
+            .bind::<diesel::sql_types::Text, _>(username)
+            .bind::<diesel::sql_types::Text, _>(format!(
+                "{}{}@open.org",
+                username, i
+            ))
+            .execute(conn)?;
+        }
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| {
+                migration.name.ends_with("_case_insensitive_user_identity")
+            })
+            .unwrap();
+        conn.batch_execute(migration.up_sql)
+    }
+
+    #[test]
+    fn case_insensitive_identity_applies_without_duplicates() {
+        let conn = establish_connection().get().unwrap();
+        assert!(
+            rerun_case_insensitive_identity(&conn, &["Bob", "alice"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn case_insensitive_identity_names_users_differing_only_by_case() {
+        let conn = establish_connection().get().unwrap();
+
+        let err = rerun_case_insensitive_identity(&conn, &["Bob", "bob"])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("users differing only by case"), "{}", err);
+        assert!(err.contains("username ["), "{}", err);
+        assert!(!err.contains("email ["), "{}", err);
+    }
+}
@@ -0,0 +1,128 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Keyset position of the last row served: the timestamp a list is sorted
+/// by and the row id breaking ties. Clients see it as an opaque token.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cursor {
+    pub at: NaiveDateTime,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(at: NaiveDateTime, id: Uuid) -> Self {
+        Cursor { at, id }
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid cursor {}", value);
+        let bytes = hex::decode(value).map_err(|_| invalid())?;
+        if bytes.len() != 24 {
+            return Err(invalid());
+        }
+        let mut micros = [0; 8];
+        micros.copy_from_slice(&bytes[..8]);
+        let micros = i64::from_be_bytes(micros);
+        let at = NaiveDateTime::from_timestamp_opt(
+            micros.div_euclid(1_000_000),
+            micros.rem_euclid(1_000_000) as u32 * 1_000,
+        )
+        .ok_or_else(invalid)?;
+        let id = Uuid::from_slice(&bytes[8..]).map_err(|_| invalid())?;
+        Ok(Cursor { at, id })
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Postgres keeps microseconds, so nothing is lost.
+        let micros = self.at.timestamp() * 1_000_000
+            + i64::from(self.at.timestamp_subsec_micros());
+        let mut bytes = micros.to_be_bytes().to_vec();
+        bytes.extend_from_slice(self.id.as_bytes());
+        write!(f, "{}", hex::encode(bytes))
+    }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(cursor: Cursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+/// Splits off the look-ahead row a page query fetches with `limit + 1`,
+/// returning the page and, when more rows follow, the position of its last
+/// row to resume from.
+pub fn into_page<T, C>(
+    mut rows: Vec<T>,
+    limit: i64,
+    cursor: impl Fn(&T) -> C,
+) -> (Vec<T>, Option<C>) {
+    if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        let next_cursor = rows.last().map(cursor);
+        (rows, next_cursor)
+    } else {
+        (rows, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn into_page_drops_look_ahead_row_and_points_at_last_kept() {
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        let (page, next_cursor) = into_page(ids.clone(), 2, |id| *id);
+        assert_eq!(page, ids[..2]);
+        assert_eq!(next_cursor, Some(ids[1]));
+
+        let (page, next_cursor) = into_page(ids.clone(), 3, |id| *id);
+        assert_eq!(page, ids);
+        assert_eq!(next_cursor, None);
+    }
+
+    #[test]
+    fn cursor_round_trips_through_its_token() {
+        let at = NaiveDateTime::parse_from_str(
+            "2020-09-13 09:34:17.123456",
+            "%Y-%m-%d %H:%M:%S%.f",
+        )
+        .unwrap();
+        let cursor = Cursor::new(at, Uuid::new_v4());
+
+        assert_eq!(cursor.to_string().parse(), Ok(cursor));
+        assert_eq!(
+            serde_json::from_value::<Cursor>(serde_json::json!(cursor))
+                .unwrap(),
+            cursor
+        );
+    }
+
+    #[test]
+    fn cursor_rejects_malformed_tokens() {
+        assert!("not-a-cursor".parse::<Cursor>().is_err());
+        assert!("00ff".parse::<Cursor>().is_err());
+        assert!(Uuid::new_v4().to_string().parse::<Cursor>().is_err());
+    }
+}
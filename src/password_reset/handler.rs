@@ -0,0 +1,410 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::result::Error;
+use diesel::{Connection, PgConnection};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{path, post, Filter};
+
+use crate::config::{AccountSettings, SessionSettings};
+use crate::db::{blocking, with_db_conn};
+use crate::error::ApiError;
+use crate::filters::{json_body, with_account, with_settings};
+use crate::mailer::{with_mailer, Email, Mailer, SharedMailer};
+use crate::session::repository::SessionRepo;
+use crate::session::token;
+use crate::shutdown::Tasks;
+use crate::user::model::{UpdateUser, User};
+use crate::user::repository::UserRepo;
+use crate::user::validation::validate_password;
+use crate::ConnectionPool;
+
+use super::model::NewPasswordReset;
+use super::repository::PasswordResetRepo;
+use super::view;
+
+/// Reset emails sent at once; further requests queue for a slot without
+/// holding a database connection.
+const MAX_CONCURRENT_RESETS: usize = 4;
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+    account: AccountSettings,
+    mailer: SharedMailer,
+    tasks: Tasks,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let sending = Arc::new(Semaphore::new(MAX_CONCURRENT_RESETS));
+    let reset_request_route = path!("password-resets")
+        .and(post())
+        .and(with_pool(pool.clone()))
+        .and(json_body())
+        .and(with_settings(settings.clone()))
+        .and(with_account(account))
+        .and(with_mailer(mailer))
+        .and(with_tasks(tasks))
+        .and(with_limit(sending))
+        .and_then(reset_request);
+
+    let reset_complete_route = path!("password-resets" / String)
+        .and(post())
+        .and(with_db_conn(pool))
+        .and(json_body())
+        .and(with_settings(settings))
+        .and_then(reset_complete);
+
+    reset_request_route.or(reset_complete_route)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResetRequestBody {
+    pub login: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResetCompleteBody {
+    pub password: String,
+}
+
+/// Emails a single-use reset link to the account behind `login`, if there
+/// is one and it wasn't sent another within `password_reset_interval`.
+/// Unknown and throttled logins succeed silently. The connection goes back
+/// to the pool before the email is sent.
+pub fn send_reset(
+    pool: &ConnectionPool,
+    mailer: &dyn Mailer,
+    login: &str,
+    settings: &SessionSettings,
+    account: &AccountSettings,
+) -> Result<(), ApiError> {
+    let conn = pool.get().map_err(|err| {
+        error!("Couldn't get a database connection: {}", err);
+        ApiError::ServiceUnavailable
+    })?;
+    let email = issue_reset(&conn, login, settings, account)?;
+    drop(conn);
+
+    if let Some(email) = email {
+        mailer.send(&email)?;
+    }
+    Ok(())
+}
+
+/// Stores the reset and returns the email carrying its link. The per-user
+/// lock keeps concurrent requests from both passing the throttle.
+fn issue_reset(
+    conn: &PgConnection,
+    login: &str,
+    settings: &SessionSettings,
+    account: &AccountSettings,
+) -> Result<Option<Email>, ApiError> {
+    conn.transaction(|| {
+        let user = match UserRepo::find_by_login(conn, login) {
+            Ok(user) => user,
+            Err(Error::NotFound) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        PasswordResetRepo::lock_user(conn, user.id)?;
+        if PasswordResetRepo::created_within(
+            conn,
+            user.id,
+            account.password_reset_interval,
+        )? {
+            info!("Skipping password reset for {}: one was just sent", user.id);
+            return Ok(None);
+        }
+
+        let token = token::generate();
+        PasswordResetRepo::create(
+            conn,
+            NewPasswordReset::new(
+                user.id,
+                &token,
+                &settings.secret,
+                account.password_reset_ttl,
+            ),
+        )?;
+        let link =
+            format!("{}/reset-password?token={}", account.app_url, token);
+        Ok(Some(view::reset_email(&user, &link)))
+    })
+}
+
+/// Always answers 202, and does the work after responding, so neither the
+/// status nor the timing reveals whether the account exists. The work is
+/// tracked so shutdown waits for emails already being sent, and capped by
+/// `sending` so a burst of requests can't drain the connection pool.
+async fn reset_request(
+    pool: ConnectionPool,
+    req: ResetRequestBody,
+    settings: SessionSettings,
+    account: AccountSettings,
+    mailer: SharedMailer,
+    tasks: Tasks,
+    sending: Arc<Semaphore>,
+) -> Result<WithStatus<Json>, Infallible> {
+    tasks.spawn_blocking(sending, move || {
+        let result =
+            send_reset(&pool, mailer.as_ref(), &req.login, &settings, &account);
+        if let Err(err) = result {
+            error!("Couldn't send password reset: {}", err.message());
+        }
+    });
+
+    let resp = view::reset_request();
+    Ok(with_status(json(&resp), StatusCode::ACCEPTED))
+}
+
+/// Sets the new password, burns every outstanding reset token for the user
+/// and signs them out everywhere.
+async fn reset_complete(
+    token: String,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: ResetCompleteBody,
+    settings: SessionSettings,
+) -> Result<WithStatus<Json>, Infallible> {
+    let token_hash = token::digest(&settings.secret, token.trim());
+    let result = blocking(move || {
+        conn.transaction(|| {
+            let reset = PasswordResetRepo::find_live(&conn, &token_hash)?;
+            let user = UserRepo::find(&conn, reset.user_id)?;
+            if let Some(err) =
+                validate_password(&req.password, &[&user.username, &user.email])
+            {
+                return Err(ApiError::Validation(vec![err]));
+            }
+
+            let changes = UpdateUser {
+                password: Some(req.password),
+                ..Default::default()
+            };
+            let user = UserRepo::update(&conn, user.id, changes)?;
+            PasswordResetRepo::delete_for_user(&conn, user.id)?;
+            SessionRepo::delete_for_user(&conn, user.id)?;
+            Ok::<User, ApiError>(user)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(user) => {
+            let resp = view::reset_complete(&user);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+fn with_pool(
+    pool: ConnectionPool,
+) -> impl Filter<Extract = (ConnectionPool,), Error = Infallible> + Clone {
+    warp::any().map(move || pool.clone())
+}
+
+fn with_tasks(
+    tasks: Tasks,
+) -> impl Filter<Extract = (Tasks,), Error = Infallible> + Clone {
+    warp::any().map(move || tasks.clone())
+}
+
+fn with_limit(
+    limit: Arc<Semaphore>,
+) -> impl Filter<Extract = (Arc<Semaphore>,), Error = Infallible> + Clone {
+    warp::any().map(move || limit.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+    use warp::test::request;
+
+    use crate::error::handle_rejection;
+    use crate::mailer::memory::MemoryMailer;
+    use crate::session::model::NewSession;
+    use crate::test_helpers::{
+        account_settings, establish_connection, session_settings, PoolProbe,
+    };
+    use crate::user::model::NewUser;
+
+    use super::*;
+
+    fn create_user(conn: &PgConnection) -> User {
+        let user = NewUser {
+            username: "bob".to_string(),
+            password: "secret-42".to_string(),
+            email: "bob@open.org".to_string(),
+            ..Default::default()
+        };
+        UserRepo::create(conn, user).expect("Failed to create user")
+    }
+
+    fn emailed_token(mailer: &MemoryMailer) -> String {
+        let body = &mailer.sent()[0].body;
+        let prefix = "/reset-password?token=";
+        body[body.find(prefix).unwrap() + prefix.len()..]
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .collect()
+    }
+
+    #[test]
+    fn send_reset_emails_known_accounts_only() {
+        let pool = establish_connection();
+        create_user(&pool.get().unwrap());
+        let mailer = MemoryMailer::default();
+
+        send_reset(
+            &pool,
+            &mailer,
+            "nobody@open.org",
+            &session_settings(),
+            &account_settings(),
+        )
+        .unwrap();
+        assert!(mailer.sent().is_empty());
+
+        send_reset(
+            &pool,
+            &mailer,
+            "Bob@Open.org",
+            &session_settings(),
+            &account_settings(),
+        )
+        .unwrap();
+        assert_eq!(mailer.sent().len(), 1);
+        assert_eq!(mailer.sent()[0].to, "bob@open.org");
+    }
+
+    #[test]
+    fn send_reset_skips_accounts_sent_one_recently() {
+        let pool = establish_connection();
+        create_user(&pool.get().unwrap());
+        let mailer = MemoryMailer::default();
+
+        for _ in 0..2 {
+            send_reset(
+                &pool,
+                &mailer,
+                "bob",
+                &session_settings(),
+                &account_settings(),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(mailer.sent().len(), 1);
+    }
+
+    #[test]
+    fn send_reset_returns_connection_before_sending() {
+        let pool = establish_connection();
+        create_user(&pool.get().unwrap());
+        let probe = PoolProbe::new(&pool);
+
+        send_reset(
+            &pool,
+            &probe,
+            "bob",
+            &session_settings(),
+            &account_settings(),
+        )
+        .unwrap();
+
+        assert_eq!(probe.in_use(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn reset_request_accepts_unknown_logins() {
+        let tasks = Tasks::default();
+        let filter = routes(
+            establish_connection(),
+            session_settings(),
+            account_settings(),
+            Arc::new(MemoryMailer::default()),
+            tasks.clone(),
+        )
+        .recover(handle_rejection);
+
+        let resp = request()
+            .method("POST")
+            .path("/password-resets")
+            .json(&json!({ "login": "nobody@open.org" }))
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        tasks.finished().await;
+    }
+
+    #[tokio::test]
+    async fn reset_complete_sets_password_once_and_signs_user_out() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        SessionRepo::create(
+            &conn,
+            NewSession::new(bob.id, "session", &session_settings()),
+        )
+        .unwrap();
+        drop(conn);
+        let mailer = MemoryMailer::default();
+        send_reset(
+            &pool,
+            &mailer,
+            "bob",
+            &session_settings(),
+            &account_settings(),
+        )
+        .unwrap();
+        let token = emailed_token(&mailer);
+        let filter = routes(
+            pool.clone(),
+            session_settings(),
+            account_settings(),
+            Arc::new(MemoryMailer::default()),
+            Tasks::default(),
+        )
+        .recover(handle_rejection);
+
+        let resp = request()
+            .method("POST")
+            .path(&format!("/password-resets/{}", token))
+            .json(&json!({ "password": "short" }))
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let resp = request()
+            .method("POST")
+            .path(&format!("/password-resets/{}", token))
+            .json(&json!({ "password": "new-secret-42" }))
+            .reply(&filter)
+            .await;
+        let body: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body["id"], json!(bob.id));
+
+        let conn = pool.get().unwrap();
+        let bob = UserRepo::find(&conn, bob.id).unwrap();
+        assert_eq!(
+            UserRepo::verify_password(&conn, &bob, "new-secret-42"),
+            Ok(true)
+        );
+        assert_eq!(SessionRepo::delete_for_user(&conn, bob.id), Ok(0));
+        drop(conn);
+
+        let resp = request()
+            .method("POST")
+            .path(&format!("/password-resets/{}", token))
+            .json(&json!({ "password": "another-secret-42" }))
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
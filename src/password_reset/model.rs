@@ -0,0 +1,38 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{Insertable, Queryable};
+use uuid::Uuid;
+
+use crate::schema::password_resets;
+use crate::session::token;
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct PasswordReset {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "password_resets"]
+pub struct NewPasswordReset {
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl NewPasswordReset {
+    pub fn new(
+        user_id: Uuid,
+        token: &str,
+        secret: &str,
+        ttl: Duration,
+    ) -> Self {
+        NewPasswordReset {
+            user_id,
+            token_hash: token::digest(secret, token),
+            expires_at: Utc::now().naive_utc() + ttl,
+        }
+    }
+}
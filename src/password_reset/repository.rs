@@ -0,0 +1,169 @@
+use chrono::{Duration, Utc};
+use diesel::dsl::{exists, now};
+use diesel::pg::data_types::PgInterval;
+use diesel::prelude::*;
+use diesel::sql_types;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::schema::password_resets;
+
+use super::model::{NewPasswordReset, PasswordReset};
+
+pub struct PasswordResetRepo;
+
+impl PasswordResetRepo {
+    pub fn create(
+        conn: &PgConnection,
+        new_reset: NewPasswordReset,
+    ) -> QueryResult<PasswordReset> {
+        diesel::insert_into(password_resets::table)
+            .values(new_reset)
+            .get_result(conn)
+    }
+
+    /// The unexpired reset behind `token_hash`, locked so two requests
+    /// can't redeem the same token. Call it inside a transaction.
+    pub fn find_live(
+        conn: &PgConnection,
+        token_hash: &str,
+    ) -> QueryResult<PasswordReset> {
+        password_resets::table
+            .filter(password_resets::token_hash.eq(token_hash))
+            .filter(password_resets::expires_at.gt(Utc::now().naive_utc()))
+            .for_update()
+            .first(conn)
+    }
+
+    /// Serializes issuing resets for one user, so concurrent requests can't
+    /// both pass `created_within` and send two emails. Held until the
+    /// surrounding transaction ends.
+    pub fn lock_user(conn: &PgConnection, user_id: Uuid) -> QueryResult<()> {
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+            .bind::<sql_types::BigInt, _>(user_lock_key(user_id))
+            .execute(conn)
+            .map(|_| ())
+    }
+
+    /// Whether a reset was issued for the user within the last `interval`.
+    pub fn created_within(
+        conn: &PgConnection,
+        user_id: Uuid,
+        interval: Duration,
+    ) -> QueryResult<bool> {
+        let interval = PgInterval::from_microseconds(
+            interval.num_microseconds().unwrap_or(i64::MAX),
+        );
+        diesel::select(exists(
+            password_resets::table
+                .filter(password_resets::user_id.eq(user_id))
+                .filter(password_resets::created_at.gt(now - interval)),
+        ))
+        .get_result(conn)
+    }
+
+    pub fn delete_for_user(
+        conn: &PgConnection,
+        user_id: Uuid,
+    ) -> QueryResult<usize> {
+        diesel::delete(
+            password_resets::table.filter(password_resets::user_id.eq(user_id)),
+        )
+        .execute(conn)
+    }
+}
+
+fn user_lock_key(user_id: Uuid) -> i64 {
+    let bits = user_id.as_u128();
+    (bits ^ (bits >> 64)) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session::token;
+    use crate::test_helpers::{create_user, establish_connection};
+
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    #[test]
+    fn find_live_ignores_expired_and_unknown_tokens() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        for (token, ttl) in &[("live", 60), ("expired", -1)] {
+            PasswordResetRepo::create(
+                &conn,
+                NewPasswordReset::new(
+                    bob.id,
+                    token,
+                    SECRET,
+                    Duration::minutes(*ttl),
+                ),
+            )
+            .unwrap();
+        }
+
+        let live =
+            PasswordResetRepo::find_live(&conn, &token::digest(SECRET, "live"))
+                .unwrap();
+        assert_eq!(live.user_id, bob.id);
+        for token in &["expired", "unknown"] {
+            let result = PasswordResetRepo::find_live(
+                &conn,
+                &token::digest(SECRET, token),
+            );
+            assert_eq!(result, Err(diesel::result::Error::NotFound));
+        }
+
+        assert_eq!(PasswordResetRepo::delete_for_user(&conn, bob.id), Ok(2));
+    }
+
+    #[test]
+    fn created_within_looks_back_over_interval() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let interval = Duration::minutes(5);
+        assert_eq!(
+            PasswordResetRepo::created_within(&conn, bob.id, interval),
+            Ok(false)
+        );
+
+        PasswordResetRepo::create(
+            &conn,
+            NewPasswordReset::new(bob.id, "token", SECRET, interval),
+        )
+        .unwrap();
+
+        assert_eq!(
+            PasswordResetRepo::created_within(&conn, bob.id, interval),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn lock_user_blocks_only_that_user() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let other = pool.get().unwrap();
+        let (bob, alice) = (Uuid::new_v4(), Uuid::new_v4());
+        let try_lock = |user_id| {
+            diesel::sql_query("SELECT pg_try_advisory_xact_lock($1) AS locked")
+                .bind::<sql_types::BigInt, _>(user_lock_key(user_id))
+                .get_result::<Locked>(&other)
+                .unwrap()
+                .locked
+        };
+
+        PasswordResetRepo::lock_user(&conn, bob).unwrap();
+
+        assert!(!try_lock(bob));
+        assert!(try_lock(alice));
+    }
+
+    #[derive(QueryableByName)]
+    struct Locked {
+        #[sql_type = "sql_types::Bool"]
+        locked: bool,
+    }
+}
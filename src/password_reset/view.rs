@@ -0,0 +1,29 @@
+use serde_json::{json, Value};
+
+use crate::mailer::Email;
+use crate::user::model::User;
+
+pub fn reset_email(user: &User, link: &str) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Hi {},\n\nSomeone asked to reset the password for your \
+             account. Choose a new one by opening the link below:\n\n{}\n\n\
+             The link works once. If you didn't ask for this, you can \
+             ignore this message and your password stays the same.",
+            user.username, link
+        ),
+    }
+}
+
+/// The same answer whether or not the account exists.
+pub fn reset_request() -> Value {
+    json!({
+        "message": "If the account exists, a reset link is on its way"
+    })
+}
+
+pub fn reset_complete(user: &User) -> Value {
+    json!({ "id": user.id })
+}
@@ -1,12 +1,41 @@
 use std::convert::Infallible;
+use std::time::Duration;
 
+use diesel::{sql_query, RunQueryDsl};
 use serde::Serialize;
-use warp::reply::Json;
+use serde_json::{json, Value};
+use warp::http::StatusCode;
+use warp::reply::{with_status, Json, WithStatus};
 use warp::{get, path, Filter, Reply};
 
+use crate::db::blocking;
+use crate::error::ApiError;
+use crate::migrations;
+use crate::shutdown::Shutdown;
+use crate::ConnectionPool;
+
+/// Upper bound on how long readiness waits for a pooled connection, so a
+/// saturated pool fails the probe instead of hanging it.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub fn routes(
+    pool: ConnectionPool,
+    shutdown: Shutdown,
 ) -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
-    path!("ping").and(get()).and_then(handler)
+    let ping_route = path!("ping")
+        .and(get())
+        .and(with_shutdown(shutdown.clone()))
+        .and_then(handler);
+
+    let live_route = path!("health" / "live").and(get()).and_then(live);
+
+    let ready_route = path!("health" / "ready")
+        .and(get())
+        .and(warp::any().map(move || pool.clone()))
+        .and(with_shutdown(shutdown))
+        .and_then(ready);
+
+    ping_route.or(live_route).or(ready_route)
 }
 
 #[derive(Serialize)]
@@ -14,21 +43,115 @@ struct Response {
     success: bool,
 }
 
-pub async fn handler() -> Result<Json, Infallible> {
-    let resp = Response { success: true };
-    Ok(warp::reply::json(&resp))
+/// Fails with 503 once shutdown starts so load balancers drain this instance.
+pub async fn handler(
+    shutdown: Shutdown,
+) -> Result<WithStatus<Json>, Infallible> {
+    let (success, status) = if shutdown.is_draining() {
+        (false, StatusCode::SERVICE_UNAVAILABLE)
+    } else {
+        (true, StatusCode::OK)
+    };
+    let resp = Response { success };
+    Ok(with_status(warp::reply::json(&resp), status))
+}
+
+/// The process is up and serving; says nothing about its dependencies.
+async fn live() -> Result<Json, Infallible> {
+    Ok(warp::reply::json(&json!({ "status": "up" })))
+}
+
+/// Checks every dependency a request needs, answering 503 with per-component
+/// detail when any of them is down.
+async fn ready(
+    pool: ConnectionPool,
+    shutdown: Shutdown,
+) -> Result<WithStatus<Json>, Infallible> {
+    let state = pool.state();
+    let pool_component = json!({
+        "status": "up",
+        "connections": state.connections,
+        "idle": state.idle_connections,
+        "max_size": pool.max_size(),
+    });
+    let server = json!({
+        "status": if shutdown.is_draining() { "down" } else { "up" },
+        "draining": shutdown.is_draining(),
+    });
+
+    let (database, migrations) =
+        blocking(move || Ok::<_, ApiError>(check_database(&pool)))
+            .await
+            .unwrap_or_else(|_| {
+                let err = "readiness check failed";
+                (down(err), down(err))
+            });
+
+    let components = json!({
+        "database": database,
+        "migrations": migrations,
+        "pool": pool_component,
+        "server": server,
+    });
+    let healthy = components
+        .as_object()
+        .into_iter()
+        .flat_map(|components| components.values())
+        .all(|component| component["status"] == "up");
+
+    let (status, code) = if healthy {
+        ("up", StatusCode::OK)
+    } else {
+        ("down", StatusCode::SERVICE_UNAVAILABLE)
+    };
+    let resp = json!({ "status": status, "components": components });
+    Ok(with_status(warp::reply::json(&resp), code))
+}
+
+fn check_database(pool: &ConnectionPool) -> (Value, Value) {
+    let timeout = pool.connection_timeout().min(CHECK_TIMEOUT);
+    let conn = match pool.get_timeout(timeout) {
+        Ok(conn) => conn,
+        Err(err) => return (down(err), down("no database connection")),
+    };
+
+    let database = match sql_query("SELECT 1").execute(&conn) {
+        Ok(_) => json!({ "status": "up" }),
+        Err(err) => down(err),
+    };
+    let migrations = match migrations::pending(&conn) {
+        Ok(pending) if pending.is_empty() => {
+            json!({ "status": "up", "pending": pending })
+        }
+        Ok(pending) => json!({ "status": "down", "pending": pending }),
+        Err(err) => down(err),
+    };
+    (database, migrations)
+}
+
+fn down(err: impl ToString) -> Value {
+    json!({ "status": "down", "error": err.to_string() })
+}
+
+fn with_shutdown(
+    shutdown: Shutdown,
+) -> impl Filter<Extract = (Shutdown,), Error = Infallible> + Clone {
+    warp::any().map(move || shutdown.clone())
 }
 
 #[cfg(test)]
 mod tests {
-    use serde_json::json;
-    use warp::http::StatusCode;
+    use crate::test_helpers::{establish_connection, unreachable_pool};
 
     use super::*;
 
+    fn body_json(body: &[u8]) -> Value {
+        serde_json::from_slice(body).expect("Invalid response")
+    }
+
     #[tokio::test]
     async fn test_get_success() {
-        let filter = routes();
+        let filter = routes(establish_connection(), Shutdown::default());
         let resp = warp::test::request()
             .method("GET")
             .path("/ping")
@@ -46,9 +169,24 @@ mod tests {
         assert_eq!(*resp.body(), expected_response);
     }
 
+    #[tokio::test]
+    async fn test_get_fails_while_draining() {
+        let shutdown = Shutdown::default();
+        shutdown.start_draining();
+        let filter = routes(establish_connection(), shutdown);
+        let resp = warp::test::request()
+            .method("GET")
+            .path("/ping")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(*resp.body(), json!({ "success": false }).to_string());
+    }
+
     #[tokio::test]
     async fn test_unsupported_methods() {
-        let filter = routes();
+        let filter = routes(establish_connection(), Shutdown::default());
         let resp = warp::test::request()
             .method("POST")
             .path("/ping")
@@ -57,4 +195,63 @@ mod tests {
 
         assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
+
+    #[tokio::test]
+    async fn live_succeeds_even_when_database_is_unreachable() {
+        let filter = routes(unreachable_pool(), Shutdown::default());
+        let resp = warp::test::request()
+            .path("/health/live")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body_json(resp.body()), json!({ "status": "up" }));
+    }
+
+    #[tokio::test]
+    async fn ready_reports_every_component_up() {
+        let filter = routes(establish_connection(), Shutdown::default());
+        let resp = warp::test::request()
+            .path("/health/ready")
+            .reply(&filter)
+            .await;
+        let body = body_json(resp.body());
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body["status"], "up");
+        assert_eq!(body["components"]["database"]["status"], "up");
+        assert_eq!(body["components"]["migrations"]["pending"], json!([]));
+        assert_eq!(body["components"]["pool"]["max_size"], 2);
+    }
+
+    #[tokio::test]
+    async fn ready_fails_when_database_is_unreachable() {
+        let filter = routes(unreachable_pool(), Shutdown::default());
+        let resp = warp::test::request()
+            .path("/health/ready")
+            .reply(&filter)
+            .await;
+        let body = body_json(resp.body());
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "down");
+        assert_eq!(body["components"]["database"]["status"], "down");
+        assert!(body["components"]["database"]["error"].is_string());
+        assert_eq!(body["components"]["server"]["status"], "up");
+    }
+
+    #[tokio::test]
+    async fn ready_fails_while_draining() {
+        let shutdown = Shutdown::default();
+        shutdown.start_draining();
+        let filter = routes(establish_connection(), shutdown);
+        let resp = warp::test::request()
+            .path("/health/ready")
+            .reply(&filter)
+            .await;
+        let body = body_json(resp.body());
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["components"]["server"]["draining"], true);
+    }
 }
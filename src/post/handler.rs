@@ -0,0 +1,335 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{Connection, PgConnection};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{delete, get, path, post, Filter};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::error::ApiError;
+use crate::feed::handler::with_feed;
+use crate::feed::strategy::Feed;
+use crate::filters::json_body;
+use crate::pagination::Cursor;
+use crate::reaction::repository::ReactionRepo;
+use crate::session::handler::{with_auth, with_verified_auth};
+use crate::user::model::User;
+use crate::user::repository::UserRepo;
+use crate::ConnectionPool;
+
+use super::model::{ListParams, NewPost};
+use super::repository::PostRepo;
+use super::{validation, view};
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+    feed: Feed,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let post_create_route = path!("posts")
+        .and(post())
+        .and(with_verified_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(json_body())
+        .and(with_feed(feed))
+        .and_then(post_create);
+
+    let post_details_route = path!("posts" / Uuid)
+        .and(get())
+        .and(with_db_conn(pool.clone()))
+        .and_then(post_details);
+
+    let user_posts_route = path!("users" / Uuid / "posts")
+        .and(get())
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool.clone()))
+        .and_then(user_posts);
+
+    let post_delete_route = path!("posts" / Uuid)
+        .and(delete())
+        .and(with_auth(pool.clone(), settings))
+        .and(with_db_conn(pool))
+        .and_then(post_delete);
+
+    post_create_route
+        .or(post_details_route)
+        .or(user_posts_route)
+        .or(post_delete_route)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RequestBody {
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<Cursor>,
+}
+
+async fn post_create(
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: RequestBody,
+    feed: Feed,
+) -> Result<WithStatus<Json>, Infallible> {
+    let req = match validation::validate_new_post(req) {
+        Ok(req) => req,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let new_post = NewPost {
+        author_id: current_user.id,
+        body: req.body,
+    };
+    let result = blocking(move || {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let post = PostRepo::create(&conn, new_post)?;
+            feed.on_post_created(&conn, &post)?;
+            Ok(post)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(post) => {
+            let resp = view::post_create(&post);
+            Ok(with_status(json(&resp), StatusCode::CREATED))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn post_details(
+    id: Uuid,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        let post = PostRepo::find(&conn, id)?;
+        let counts = ReactionRepo::counts(&conn, &[id])?;
+        Ok::<_, diesel::result::Error>((post, counts[&id]))
+    })
+    .await;
+
+    match result {
+        Ok((post, counts)) => {
+            let resp = view::post_details(&post, &counts);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn user_posts(
+    user_id: Uuid,
+    query: IndexQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        UserRepo::find(&conn, user_id)?;
+        let (posts, next_cursor) =
+            PostRepo::list_by_author(&conn, user_id, &params)?;
+        Ok::<_, diesel::result::Error>((
+            ReactionRepo::with_counts(&conn, posts)?,
+            next_cursor,
+        ))
+    })
+    .await;
+
+    match result {
+        Ok((posts, next_cursor)) => {
+            let resp = view::post_page(&posts, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn post_delete(
+    id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        let post = PostRepo::find(&conn, id)?;
+        if post.author_id != current_user.id {
+            return Err(ApiError::Forbidden);
+        }
+        Ok(PostRepo::delete(&conn, id)?)
+    })
+    .await;
+
+    match result {
+        Ok(_) => Ok(with_status(
+            json(&json!({ "success": true })),
+            StatusCode::OK,
+        )),
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use warp::Reply;
+
+    use crate::error::handle_rejection;
+    use crate::feed::strategy::StrategyKind;
+    use crate::test_helpers::{
+        create_post, create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    fn feed() -> Feed {
+        StrategyKind::Read.build()
+    }
+
+    async fn body_json(resp: WithStatus<Json>) -> (StatusCode, Value) {
+        let (parts, body) = resp.into_response().into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (parts.status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn post_create_stores_post_for_current_user() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+        let req = RequestBody {
+            body: "  hello world ".to_string(),
+        };
+
+        let resp = post_create(bob.clone(), pool.get().unwrap(), req, feed())
+            .await
+            .unwrap();
+        let (status, body) = body_json(resp).await;
+
+        let id: Uuid = body["id"].as_str().unwrap().parse().unwrap();
+        let post = PostRepo::find(&pool.get().unwrap(), id).unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(post.author_id, bob.id);
+        assert_eq!(post.body, "hello world");
+    }
+
+    #[tokio::test]
+    async fn post_create_rejects_blank_body() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+        let req = RequestBody {
+            body: " ".to_string(),
+        };
+
+        let resp = post_create(bob, pool.get().unwrap(), req, feed())
+            .await
+            .unwrap();
+        let (status, body) = body_json(resp).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["field"], "body");
+    }
+
+    #[tokio::test]
+    async fn post_details_returns_post_or_404() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let post = create_post(&conn, &create_user(&conn));
+        drop(conn);
+
+        let resp = post_details(post.id, pool.get().unwrap()).await.unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, view::post_details(&post, &Default::default()));
+
+        let resp = post_details(Uuid::new_v4(), pool.get().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn post_details_hides_posts_of_inactive_authors() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let eve = create_user(&conn);
+        let post = create_post(&conn, &eve);
+        UserRepo::schedule_deletion(&conn, eve.id).unwrap();
+        drop(conn);
+
+        let resp = post_details(post.id, pool.get().unwrap()).await.unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn user_posts_lists_posts_of_existing_user() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        drop(conn);
+
+        let resp =
+            user_posts(bob.id, IndexQuery::default(), pool.get().unwrap())
+                .await
+                .unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, view::post_page(&[(post, Default::default())], None));
+
+        let resp = user_posts(
+            Uuid::new_v4(),
+            IndexQuery::default(),
+            pool.get().unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn post_delete_is_restricted_to_author() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        drop(conn);
+
+        let resp = post_delete(post.id, alice, pool.get().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::FORBIDDEN);
+
+        let resp = post_delete(post.id, bob, pool.get().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::OK);
+        assert!(PostRepo::find(&pool.get().unwrap(), post.id).is_err());
+    }
+
+    #[tokio::test]
+    async fn post_create_route_requires_bearer_token() {
+        let filter = routes(
+            establish_connection(),
+            session_settings(),
+            StrategyKind::Read.build(),
+        )
+        .recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/posts")
+            .json(&RequestBody {
+                body: "hello".to_string(),
+            })
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,63 @@
+use chrono::NaiveDateTime;
+use diesel::{Insertable, Queryable};
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+use crate::schema::posts;
+use crate::user::model::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use super::handler::IndexQuery;
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct Post {
+    pub id: Uuid,
+    pub author_id: Uuid,
+    pub body: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "posts"]
+pub struct NewPost {
+    pub author_id: Uuid,
+    pub body: String,
+}
+
+/// Pages run newest first; `cursor` is the position of the last post
+/// already seen.
+#[derive(PartialEq, Debug)]
+pub struct ListParams {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+}
+
+impl From<IndexQuery> for ListParams {
+    fn from(query: IndexQuery) -> Self {
+        ListParams {
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            cursor: query.cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_params_clamp_limit() {
+        let params: ListParams = IndexQuery {
+            limit: Some(1000),
+            cursor: None,
+        }
+        .into();
+        assert_eq!(params.limit, MAX_PAGE_SIZE);
+
+        let params: ListParams = IndexQuery::default().into();
+        assert_eq!(params.limit, DEFAULT_PAGE_SIZE);
+    }
+}
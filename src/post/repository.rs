@@ -0,0 +1,173 @@
+use diesel::prelude::*;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::{into_page, Cursor};
+use crate::schema::posts;
+use crate::schema::posts::dsl::*;
+use crate::user::repository::active_ids;
+
+use super::model::{ListParams, NewPost, Post};
+
+pub struct PostRepo;
+
+impl PostRepo {
+    pub fn create(conn: &PgConnection, new_post: NewPost) -> QueryResult<Post> {
+        diesel::insert_into(posts::table)
+            .values(new_post)
+            .get_result(conn)
+    }
+
+    /// Posts by deactivated or deleted accounts aren't found.
+    pub fn find(conn: &PgConnection, post_id: Uuid) -> QueryResult<Post> {
+        posts
+            .find(post_id)
+            .filter(author_id.eq_any(active_ids()))
+            .first(conn)
+    }
+
+    /// Newest first, keyed on `(created_at, id)`; the second value is the
+    /// cursor for the following page, if any. Empty while the author is
+    /// inactive.
+    pub fn list_by_author(
+        conn: &PgConnection,
+        author: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<Post>, Option<Cursor>)> {
+        let mut query = posts
+            .filter(author_id.eq(author))
+            .filter(author_id.eq_any(active_ids()))
+            .order((created_at.desc(), id.desc()))
+            .into_boxed();
+
+        if let Some(cursor) = params.cursor {
+            query = query.filter(
+                created_at
+                    .lt(cursor.at)
+                    .or(created_at.eq(cursor.at).and(id.lt(cursor.id))),
+            );
+        }
+
+        let page = query.limit(params.limit + 1).load::<Post>(conn)?;
+        Ok(into_page(page, params.limit, |post| {
+            Cursor::new(post.created_at, post.id)
+        }))
+    }
+
+    pub fn delete(conn: &PgConnection, post_id: Uuid) -> QueryResult<usize> {
+        diesel::delete(posts.find(post_id)).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{create_user, establish_connection};
+    use crate::user::model::User;
+    use crate::user::repository::UserRepo;
+
+    use super::*;
+
+    fn create_post(conn: &PgConnection, author: &User, text: &str) -> Post {
+        let new_post = NewPost {
+            author_id: author.id,
+            body: text.to_string(),
+        };
+        PostRepo::create(conn, new_post).expect("Failed to create post")
+    }
+
+    #[test]
+    fn create_and_find_post() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+
+        let post = create_post(&conn, &bob, "hello");
+
+        assert_eq!(post.author_id, bob.id);
+        assert_eq!(PostRepo::find(&conn, post.id).unwrap(), post);
+    }
+
+    #[test]
+    fn list_by_author_pages_newest_first() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        for text in &["one", "two", "three"] {
+            create_post(&conn, &bob, text);
+        }
+        create_post(&conn, &alice, "not bob's");
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+        };
+        let (first, cursor) =
+            PostRepo::list_by_author(&conn, bob.id, &params).unwrap();
+        let params = ListParams { limit: 2, cursor };
+        let (second, cursor) =
+            PostRepo::list_by_author(&conn, bob.id, &params).unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 1);
+        assert_eq!(cursor, None);
+        assert!(first.iter().chain(&second).all(|p| p.author_id == bob.id));
+        assert!(!first.contains(&second[0]));
+    }
+
+    #[test]
+    fn list_by_author_resumes_after_cursor_post_is_deleted() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        for text in &["one", "two", "three"] {
+            create_post(&conn, &bob, text);
+        }
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+        };
+        let (first, cursor) =
+            PostRepo::list_by_author(&conn, bob.id, &params).unwrap();
+        PostRepo::delete(&conn, first[1].id).unwrap();
+        let params = ListParams { limit: 2, cursor };
+        let (second, cursor) =
+            PostRepo::list_by_author(&conn, bob.id, &params).unwrap();
+
+        assert_eq!(second.len(), 1);
+        assert_eq!(cursor, None);
+        assert!(!first.contains(&second[0]));
+    }
+
+    #[test]
+    fn posts_of_inactive_authors_are_hidden() {
+        let conn = establish_connection().get().unwrap();
+        let eve = create_user(&conn);
+        let post = create_post(&conn, &eve, "hello");
+        UserRepo::deactivate(&conn, eve.id).unwrap();
+
+        let params = ListParams {
+            limit: 10,
+            cursor: None,
+        };
+        let (listed, _) =
+            PostRepo::list_by_author(&conn, eve.id, &params).unwrap();
+
+        assert_eq!(
+            PostRepo::find(&conn, post.id),
+            Err(diesel::result::Error::NotFound)
+        );
+        assert!(listed.is_empty());
+    }
+
+    #[test]
+    fn delete_removes_post() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob, "hello");
+
+        assert_eq!(PostRepo::delete(&conn, post.id).unwrap(), 1);
+        assert_eq!(
+            PostRepo::find(&conn, post.id),
+            Err(diesel::result::Error::NotFound)
+        );
+    }
+}
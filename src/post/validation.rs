@@ -0,0 +1,49 @@
+use crate::error::{ApiError, FieldError};
+
+use super::handler::RequestBody;
+
+const BODY_MAX_LENGTH: usize = 5000;
+
+/// Trims the post body and rejects it when empty or too long.
+pub fn validate_new_post(req: RequestBody) -> Result<RequestBody, ApiError> {
+    let body = req.body.trim().to_string();
+    let length = body.chars().count();
+
+    if length == 0 {
+        Err(ApiError::Validation(vec![FieldError::new(
+            "body",
+            "must not be blank",
+        )]))
+    } else if length > BODY_MAX_LENGTH {
+        Err(ApiError::Validation(vec![FieldError::new(
+            "body",
+            format!("must be at most {} characters", BODY_MAX_LENGTH),
+        )]))
+    } else {
+        Ok(RequestBody { body })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(body: &str) -> RequestBody {
+        RequestBody {
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn validate_new_post_trims_body() {
+        let actual = validate_new_post(request("  hello\n")).unwrap();
+        assert_eq!(actual.body, "hello");
+    }
+
+    #[test]
+    fn validate_new_post_rejects_blank_or_oversized_body() {
+        assert!(validate_new_post(request("   ")).is_err());
+        assert!(validate_new_post(request(&"a".repeat(5001))).is_err());
+        assert!(validate_new_post(request(&"a".repeat(5000))).is_ok());
+    }
+}
@@ -0,0 +1,83 @@
+use serde_json::{json, Value};
+
+use crate::pagination::Cursor;
+use crate::reaction::model::ReactionCounts;
+
+use super::model::Post;
+
+pub fn post_details(post: &Post, reactions: &ReactionCounts) -> Value {
+    json!({
+        "id": post.id,
+        "author_id": post.author_id,
+        "body": post.body,
+        "created_at": post.created_at,
+        "updated_at": post.updated_at,
+        "reactions": reactions
+    })
+}
+
+pub fn post_page(
+    posts: &[(Post, ReactionCounts)],
+    next_cursor: Option<Cursor>,
+) -> Value {
+    let data: Vec<Value> = posts
+        .iter()
+        .map(|(post, reactions)| post_details(post, reactions))
+        .collect();
+    json!({
+        "data": data,
+        "next_cursor": next_cursor
+    })
+}
+
+pub fn post_create(post: &Post) -> Value {
+    json!({ "id": post.id })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn fake_post() -> Post {
+        Post {
+            id: Uuid::new_v4(),
+            author_id: Uuid::new_v4(),
+            body: "hello".to_string(),
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+        }
+    }
+
+    #[test]
+    fn post_page_wraps_posts_and_cursor() {
+        let post = fake_post();
+        let cursor = Cursor::new(post.created_at, post.id);
+
+        let expected = json!({
+            "data": [{
+                "id": post.id,
+                "author_id": post.author_id,
+                "body": "hello",
+                "created_at": post.created_at,
+                "updated_at": post.updated_at,
+                "reactions": { "like": 1, "love": 0, "laugh": 0 }
+            }],
+            "next_cursor": cursor.to_string()
+        });
+
+        let reactions = ReactionCounts {
+            like: 1,
+            ..Default::default()
+        };
+        assert_eq!(post_page(&[(post, reactions)], Some(cursor)), expected);
+    }
+
+    #[test]
+    fn post_create_returns_id() {
+        let post = fake_post();
+        assert_eq!(post_create(&post), json!({ "id": post.id }));
+    }
+}
@@ -0,0 +1,234 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{delete, get, path, put, Filter};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::filters::json_body;
+use crate::pagination::Cursor;
+use crate::post::repository::PostRepo;
+use crate::session::handler::with_auth;
+use crate::user::model::User;
+use crate::ConnectionPool;
+
+use super::model::{ListParams, NewReaction, ReactionKind};
+use super::repository::ReactionRepo;
+use super::view;
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let react_route = path!("posts" / Uuid / "reactions")
+        .and(put())
+        .and(with_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(json_body())
+        .and_then(react);
+
+    let unreact_route = path!("posts" / Uuid / "reactions")
+        .and(delete())
+        .and(with_auth(pool.clone(), settings))
+        .and(with_db_conn(pool.clone()))
+        .and_then(unreact);
+
+    let reaction_index_route = path!("posts" / Uuid / "reactions")
+        .and(get())
+        .and(warp::query::<IndexQuery>())
+        .and(with_db_conn(pool))
+        .and_then(reaction_index);
+
+    react_route.or(unreact_route).or(reaction_index_route)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RequestBody {
+    pub kind: ReactionKind,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<Cursor>,
+    pub kind: Option<ReactionKind>,
+}
+
+async fn react(
+    post_id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: RequestBody,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        PostRepo::find(&conn, post_id)?;
+        let new_reaction = NewReaction {
+            post_id,
+            user_id: current_user.id,
+            kind: req.kind,
+        };
+        let reaction = ReactionRepo::react(&conn, new_reaction)?;
+        let counts = ReactionRepo::counts(&conn, &[post_id])?;
+        Ok::<_, diesel::result::Error>((reaction, counts[&post_id]))
+    })
+    .await;
+
+    match result {
+        Ok((reaction, counts)) => {
+            let resp =
+                view::reaction_status(post_id, Some(reaction.kind), &counts);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn unreact(
+    post_id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        PostRepo::find(&conn, post_id)?;
+        ReactionRepo::unreact(&conn, post_id, current_user.id)?;
+        let counts = ReactionRepo::counts(&conn, &[post_id])?;
+        Ok::<_, diesel::result::Error>(counts[&post_id])
+    })
+    .await;
+
+    match result {
+        Ok(counts) => {
+            let resp = view::reaction_status(post_id, None, &counts);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn reaction_index(
+    post_id: Uuid,
+    query: IndexQuery,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    let result = blocking(move || {
+        PostRepo::find(&conn, post_id)?;
+        ReactionRepo::list(&conn, post_id, &params)
+    })
+    .await;
+
+    match result {
+        Ok((entries, next_cursor)) => {
+            let resp = view::reaction_page(&entries, next_cursor);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use warp::Reply;
+
+    use crate::error::handle_rejection;
+    use crate::test_helpers::{
+        create_post, create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    async fn body_json(resp: WithStatus<Json>) -> (StatusCode, Value) {
+        let (parts, body) = resp.into_response().into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (parts.status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn react_then_unreact_returns_updated_counts() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        drop(conn);
+
+        for _ in 0..2 {
+            let req = RequestBody {
+                kind: ReactionKind::Love,
+            };
+            let resp = react(post.id, bob.clone(), pool.get().unwrap(), req)
+                .await
+                .unwrap();
+            let (status, body) = body_json(resp).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["kind"], "love");
+            assert_eq!(
+                body["reactions"],
+                json!({ "like": 0, "love": 1, "laugh": 0 })
+            );
+        }
+
+        let resp = unreact(post.id, bob, pool.get().unwrap()).await.unwrap();
+        let (status, body) = body_json(resp).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["kind"], Value::Null);
+        assert_eq!(body["reactions"]["love"], 0);
+    }
+
+    #[tokio::test]
+    async fn react_returns_404_for_unknown_post() {
+        let pool = establish_connection();
+        let bob = create_user(&pool.get().unwrap());
+        let req = RequestBody {
+            kind: ReactionKind::Like,
+        };
+
+        let resp = react(Uuid::new_v4(), bob, pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reaction_index_lists_who_reacted() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        let new_reaction = NewReaction {
+            post_id: post.id,
+            user_id: bob.id,
+            kind: ReactionKind::Laugh,
+        };
+        ReactionRepo::react(&conn, new_reaction).unwrap();
+        drop(conn);
+
+        let resp =
+            reaction_index(post.id, IndexQuery::default(), pool.get().unwrap())
+                .await
+                .unwrap();
+        let (status, body) = body_json(resp).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["username"], bob.username);
+        assert_eq!(body["data"][0]["kind"], "laugh");
+    }
+
+    #[tokio::test]
+    async fn reaction_index_route_rejects_unknown_kind() {
+        let filter = routes(establish_connection(), session_settings())
+            .recover(handle_rejection);
+        let resp = warp::test::request()
+            .method("GET")
+            .path(&format!("/posts/{}/reactions?kind=angry", Uuid::new_v4()))
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
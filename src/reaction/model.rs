@@ -0,0 +1,147 @@
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::{Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+use crate::schema::reactions;
+use crate::user::model::{User, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+use super::handler::IndexQuery;
+
+/// Diesel's handle on the `reaction_kind` Postgres enum.
+#[derive(SqlType)]
+#[postgres(type_name = "reaction_kind")]
+pub struct ReactionKindType;
+
+#[derive(
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+)]
+#[sql_type = "ReactionKindType"]
+#[serde(rename_all = "lowercase")]
+pub enum ReactionKind {
+    Like,
+    Love,
+    Laugh,
+}
+
+impl ToSql<ReactionKindType, Pg> for ReactionKind {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        let label: &[u8] = match self {
+            ReactionKind::Like => b"like",
+            ReactionKind::Love => b"love",
+            ReactionKind::Laugh => b"laugh",
+        };
+        out.write_all(label)?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<ReactionKindType, Pg> for ReactionKind {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"like" => Ok(ReactionKind::Like),
+            b"love" => Ok(ReactionKind::Love),
+            b"laugh" => Ok(ReactionKind::Laugh),
+            _ => Err("Unrecognized reaction_kind variant".into()),
+        }
+    }
+}
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct Reaction {
+    pub post_id: Uuid,
+    pub user_id: Uuid,
+    pub kind: ReactionKind,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "reactions"]
+pub struct NewReaction {
+    pub post_id: Uuid,
+    pub user_id: Uuid,
+    pub kind: ReactionKind,
+}
+
+/// Per-kind totals for one post, serialized as `{"like": n, ...}`.
+#[derive(Serialize, PartialEq, Clone, Copy, Default, Debug)]
+pub struct ReactionCounts {
+    pub like: i64,
+    pub love: i64,
+    pub laugh: i64,
+}
+
+impl ReactionCounts {
+    pub fn add(&mut self, kind: ReactionKind, count: i64) {
+        match kind {
+            ReactionKind::Like => self.like += count,
+            ReactionKind::Love => self.love += count,
+            ReactionKind::Laugh => self.laugh += count,
+        }
+    }
+}
+
+/// A user who reacted and how.
+pub type ReactionEntry = (User, ReactionKind, NaiveDateTime);
+
+/// Pages run newest reaction first; `cursor` is the position of the last
+/// reaction already seen.
+#[derive(PartialEq, Debug)]
+pub struct ListParams {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+    pub kind: Option<ReactionKind>,
+}
+
+impl From<IndexQuery> for ListParams {
+    fn from(query: IndexQuery) -> Self {
+        ListParams {
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            cursor: query.cursor,
+            kind: query.kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reaction_kind_uses_lowercase_names() {
+        assert_eq!(json!(ReactionKind::Laugh), json!("laugh"));
+        assert_eq!(
+            serde_json::from_value::<ReactionKind>(json!("love")).unwrap(),
+            ReactionKind::Love
+        );
+    }
+
+    #[test]
+    fn reaction_counts_add_per_kind() {
+        let mut counts = ReactionCounts::default();
+        counts.add(ReactionKind::Like, 2);
+        counts.add(ReactionKind::Laugh, 1);
+
+        assert_eq!(json!(counts), json!({ "like": 2, "love": 0, "laugh": 1 }));
+    }
+}
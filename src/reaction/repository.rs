@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::pagination::{into_page, Cursor};
+use crate::post::model::Post;
+use crate::schema::{reactions, users};
+use crate::user::repository::active;
+
+use super::model::{
+    ListParams, NewReaction, Reaction, ReactionCounts, ReactionEntry,
+    ReactionKind,
+};
+
+pub struct ReactionRepo;
+
+impl ReactionRepo {
+    /// Sets the user's reaction to a post, replacing any earlier kind, so
+    /// repeating the same call leaves the same state.
+    pub fn react(
+        conn: &PgConnection,
+        new_reaction: NewReaction,
+    ) -> QueryResult<Reaction> {
+        let kind = new_reaction.kind;
+        diesel::insert_into(reactions::table)
+            .values(new_reaction)
+            .on_conflict((reactions::post_id, reactions::user_id))
+            .do_update()
+            .set(reactions::kind.eq(kind))
+            .get_result(conn)
+    }
+
+    pub fn unreact(
+        conn: &PgConnection,
+        post_id: Uuid,
+        user_id: Uuid,
+    ) -> QueryResult<usize> {
+        diesel::delete(reactions::table.find((post_id, user_id))).execute(conn)
+    }
+
+    /// Totals per post for every id given; posts without reactions map to
+    /// zero counts. Reactions by deactivated or deleted accounts aren't
+    /// counted.
+    pub fn counts(
+        conn: &PgConnection,
+        post_ids: &[Uuid],
+    ) -> QueryResult<HashMap<Uuid, ReactionCounts>> {
+        let rows = reactions::table
+            .inner_join(users::table)
+            .filter(reactions::post_id.eq_any(post_ids))
+            .filter(active())
+            .group_by((reactions::post_id, reactions::kind))
+            // Diesel 1.4 can't mix aggregates with grouped columns.
+            .select((
+                reactions::post_id,
+                reactions::kind,
+                sql::<BigInt>("count(*)"),
+            ))
+            .load::<(Uuid, ReactionKind, i64)>(conn)?;
+
+        let mut counts: HashMap<Uuid, ReactionCounts> = post_ids
+            .iter()
+            .map(|post_id| (*post_id, ReactionCounts::default()))
+            .collect();
+        for (post_id, kind, count) in rows {
+            counts.entry(post_id).or_default().add(kind, count);
+        }
+        Ok(counts)
+    }
+
+    /// Pairs each post with its reaction totals, keeping the order given.
+    pub fn with_counts(
+        conn: &PgConnection,
+        posts: Vec<Post>,
+    ) -> QueryResult<Vec<(Post, ReactionCounts)>> {
+        let ids: Vec<Uuid> = posts.iter().map(|post| post.id).collect();
+        let counts = Self::counts(conn, &ids)?;
+        Ok(posts
+            .into_iter()
+            .map(|post| {
+                let post_counts =
+                    counts.get(&post.id).copied().unwrap_or_default();
+                (post, post_counts)
+            })
+            .collect())
+    }
+
+    /// Users who reacted to a post, newest first. The second value is the
+    /// cursor for the following page, if any.
+    pub fn list(
+        conn: &PgConnection,
+        post_id: Uuid,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<ReactionEntry>, Option<Cursor>)> {
+        let mut query = reactions::table
+            .inner_join(users::table)
+            .filter(reactions::post_id.eq(post_id))
+            .filter(active())
+            .select((
+                users::all_columns,
+                reactions::kind,
+                reactions::created_at,
+            ))
+            .order((reactions::created_at.desc(), reactions::user_id.desc()))
+            .into_boxed();
+
+        if let Some(kind) = params.kind {
+            query = query.filter(reactions::kind.eq(kind));
+        }
+
+        if let Some(cursor) = params.cursor {
+            query = query.filter(
+                reactions::created_at.lt(cursor.at).or(reactions::created_at
+                    .eq(cursor.at)
+                    .and(reactions::user_id.lt(cursor.id))),
+            );
+        }
+
+        let page = query.limit(params.limit + 1).load::<ReactionEntry>(conn)?;
+        Ok(into_page(page, params.limit, |(user, _, reacted_at)| {
+            Cursor::new(*reacted_at, user.id)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{create_post, create_user, establish_connection};
+    use crate::user::model::User;
+    use crate::user::repository::UserRepo;
+
+    use super::*;
+
+    fn react(
+        conn: &PgConnection,
+        post: &Post,
+        user: &User,
+        kind: ReactionKind,
+    ) {
+        let new_reaction = NewReaction {
+            post_id: post.id,
+            user_id: user.id,
+            kind,
+        };
+        ReactionRepo::react(conn, new_reaction).expect("Failed to react");
+    }
+
+    #[test]
+    fn react_is_idempotent_and_replaces_kind() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+
+        react(&conn, &post, &bob, ReactionKind::Like);
+        react(&conn, &post, &bob, ReactionKind::Like);
+        react(&conn, &post, &bob, ReactionKind::Love);
+
+        let counts = ReactionRepo::counts(&conn, &[post.id]).unwrap();
+        let expected = ReactionCounts {
+            like: 0,
+            love: 1,
+            laugh: 0,
+        };
+        assert_eq!(counts[&post.id], expected);
+    }
+
+    #[test]
+    fn counts_cover_posts_without_reactions() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let popular = create_post(&conn, &bob);
+        let quiet = create_post(&conn, &bob);
+        react(&conn, &popular, &bob, ReactionKind::Like);
+        react(&conn, &popular, &alice, ReactionKind::Like);
+
+        let posts =
+            ReactionRepo::with_counts(&conn, vec![popular, quiet]).unwrap();
+
+        assert_eq!(posts[0].1.like, 2);
+        assert_eq!(posts[1].1, ReactionCounts::default());
+    }
+
+    #[test]
+    fn counts_skip_inactive_users() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        react(&conn, &post, &bob, ReactionKind::Like);
+        react(&conn, &post, &alice, ReactionKind::Like);
+        UserRepo::deactivate(&conn, alice.id).unwrap();
+
+        let counts = ReactionRepo::counts(&conn, &[post.id]).unwrap();
+
+        assert_eq!(counts[&post.id].like, 1);
+    }
+
+    #[test]
+    fn unreact_removes_reaction() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        react(&conn, &post, &bob, ReactionKind::Laugh);
+
+        assert_eq!(ReactionRepo::unreact(&conn, post.id, bob.id).unwrap(), 1);
+        assert_eq!(ReactionRepo::unreact(&conn, post.id, bob.id).unwrap(), 0);
+    }
+
+    #[test]
+    fn list_filters_by_kind_and_pages() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        let fans: Vec<User> = (0..3).map(|_| create_user(&conn)).collect();
+        for fan in &fans {
+            react(&conn, &post, fan, ReactionKind::Love);
+        }
+        react(&conn, &post, &bob, ReactionKind::Like);
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+            kind: Some(ReactionKind::Love),
+        };
+        let (first, cursor) =
+            ReactionRepo::list(&conn, post.id, &params).unwrap();
+        let params = ListParams { cursor, ..params };
+        let (second, cursor) =
+            ReactionRepo::list(&conn, post.id, &params).unwrap();
+
+        assert_eq!(first.len() + second.len(), 3);
+        assert_eq!(cursor, None);
+        assert!(first
+            .iter()
+            .chain(&second)
+            .all(|(user, kind, _)| *kind == ReactionKind::Love
+                && user.id != bob.id));
+    }
+
+    #[test]
+    fn list_resumes_after_cursor_reaction_is_removed() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        let fans: Vec<User> = (0..3).map(|_| create_user(&conn)).collect();
+        for fan in &fans {
+            react(&conn, &post, fan, ReactionKind::Love);
+        }
+
+        let params = ListParams {
+            limit: 2,
+            cursor: None,
+            kind: None,
+        };
+        let (first, cursor) =
+            ReactionRepo::list(&conn, post.id, &params).unwrap();
+        ReactionRepo::unreact(&conn, post.id, first[1].0.id).unwrap();
+        let params = ListParams { cursor, ..params };
+        let (second, cursor) =
+            ReactionRepo::list(&conn, post.id, &params).unwrap();
+
+        assert_eq!(second.len(), 1);
+        assert_eq!(cursor, None);
+        assert!(first.iter().all(|(user, _, _)| user.id != second[0].0.id));
+    }
+
+    #[test]
+    fn list_skips_inactive_users() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        let alice = create_user(&conn);
+        let post = create_post(&conn, &bob);
+        react(&conn, &post, &bob, ReactionKind::Like);
+        react(&conn, &post, &alice, ReactionKind::Like);
+        UserRepo::schedule_deletion(&conn, alice.id).unwrap();
+
+        let params = ListParams {
+            limit: 10,
+            cursor: None,
+            kind: None,
+        };
+        let (reacted, _) = ReactionRepo::list(&conn, post.id, &params).unwrap();
+
+        assert_eq!(reacted.len(), 1);
+        assert_eq!(reacted[0].0.id, bob.id);
+    }
+}
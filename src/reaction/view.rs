@@ -0,0 +1,66 @@
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::pagination::Cursor;
+
+use super::model::{ReactionCounts, ReactionEntry, ReactionKind};
+
+pub fn reaction_status(
+    post_id: Uuid,
+    kind: Option<ReactionKind>,
+    counts: &ReactionCounts,
+) -> Value {
+    json!({
+        "post_id": post_id,
+        "kind": kind,
+        "reactions": counts
+    })
+}
+
+pub fn reaction_page(
+    entries: &[ReactionEntry],
+    next_cursor: Option<Cursor>,
+) -> Value {
+    let data: Vec<Value> = entries
+        .iter()
+        .map(|(user, kind, reacted_at)| {
+            json!({
+                "id": user.id,
+                "username": user.username,
+                "kind": kind,
+                "reacted_at": reacted_at
+            })
+        })
+        .collect();
+
+    json!({
+        "data": data,
+        "next_cursor": next_cursor
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reaction_status_includes_kind_and_totals() {
+        let post_id = Uuid::new_v4();
+        let counts = ReactionCounts {
+            like: 1,
+            love: 0,
+            laugh: 2,
+        };
+
+        let expected = json!({
+            "post_id": post_id,
+            "kind": "laugh",
+            "reactions": { "like": 1, "love": 0, "laugh": 2 }
+        });
+
+        assert_eq!(
+            reaction_status(post_id, Some(ReactionKind::Laugh), &counts),
+            expected
+        );
+    }
+}
@@ -1,26 +1,86 @@
-use std::env;
+use std::convert::Infallible;
 
-use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::r2d2::{ConnectionManager, Pool, PoolError};
 use diesel::PgConnection;
 use warp::{Filter, Reply};
 
+use crate::comment;
+use crate::config::Config;
+use crate::conversation;
 use crate::echo;
+use crate::error;
+use crate::feed;
+use crate::follow;
+use crate::password_reset;
 use crate::ping;
+use crate::post;
+use crate::reaction;
+use crate::session;
+use crate::shutdown::{Shutdown, Tasks};
 use crate::user;
+use crate::verification;
 use crate::ConnectionPool;
 
-fn establish_connection() -> ConnectionPool {
-    dotenv::dotenv().ok();
-    let database_url =
-        env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let manager = ConnectionManager::<PgConnection>::new(&database_url);
-    Pool::new(manager).expect("Postgres connection pool couldn't be created")
+pub fn establish_connection(
+    config: &Config,
+) -> Result<ConnectionPool, PoolError> {
+    let manager = ConnectionManager::<PgConnection>::new(&config.database_url);
+    Pool::builder()
+        .max_size(config.pool_size)
+        .connection_timeout(config.connection_timeout())
+        .build(manager)
 }
 
 pub fn routes(
-) -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
-    let db_pool = establish_connection();
-    ping::routes()
+    db_pool: ConnectionPool,
+    config: &Config,
+    shutdown: Shutdown,
+    tasks: Tasks,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+    let feed = config.feed_strategy.build();
+    let mailer = config.mailer.build(&config.mail());
+    ping::routes(db_pool.clone(), shutdown)
         .or(echo::routes())
-        .or(user::handler::routes(db_pool))
+        .or(user::handler::routes(
+            db_pool.clone(),
+            config.session(),
+            config.account(),
+            mailer.clone(),
+        ))
+        .or(post::handler::routes(
+            db_pool.clone(),
+            config.session(),
+            feed.clone(),
+        ))
+        .or(follow::handler::routes(
+            db_pool.clone(),
+            config.session(),
+            feed.clone(),
+        ))
+        .or(reaction::handler::routes(db_pool.clone(), config.session()))
+        .or(comment::handler::routes(db_pool.clone(), config.session()))
+        .or(conversation::handler::routes(
+            db_pool.clone(),
+            config.session(),
+        ))
+        .or(feed::handler::routes(
+            db_pool.clone(),
+            config.session(),
+            feed,
+        ))
+        .or(verification::handler::routes(
+            db_pool.clone(),
+            config.session(),
+            config.account(),
+            mailer.clone(),
+        ))
+        .or(password_reset::handler::routes(
+            db_pool.clone(),
+            config.session(),
+            config.account(),
+            mailer,
+            tasks,
+        ))
+        .or(session::handler::routes(db_pool, config.session()))
+        .recover(error::handle_rejection)
 }
@@ -1,8 +1,162 @@
+table! {
+    use diesel::sql_types::*;
+    use crate::comment::model::CommentSubjectType;
+
+    comments (id) {
+        id -> Uuid,
+        subject_type -> CommentSubjectType,
+        subject_id -> Uuid,
+        parent_comment_id -> Nullable<Uuid>,
+        author_id -> Nullable<Uuid>,
+        body -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    conversation_members (conversation_id, user_id) {
+        conversation_id -> Uuid,
+        user_id -> Uuid,
+        joined_at -> Timestamp,
+    }
+}
+
+table! {
+    conversations (id) {
+        id -> Uuid,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        last_message_at -> Timestamp,
+    }
+}
+
+table! {
+    email_verifications (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        email -> Varchar,
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    follows (follower_id, followee_id) {
+        follower_id -> Uuid,
+        followee_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    messages (id) {
+        id -> Uuid,
+        conversation_id -> Uuid,
+        sender_id -> Uuid,
+        body -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    password_resets (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    posts (id) {
+        id -> Uuid,
+        author_id -> Uuid,
+        body -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::reaction::model::ReactionKindType;
+
+    reactions (post_id, user_id) {
+        post_id -> Uuid,
+        user_id -> Uuid,
+        kind -> ReactionKindType,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    timeline_entries (user_id, post_id) {
+        user_id -> Uuid,
+        post_id -> Uuid,
+        author_id -> Uuid,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     users (id) {
         id -> Uuid,
         username -> Varchar,
         email -> Varchar,
         password -> Varchar,
+        display_name -> Nullable<Varchar>,
+        bio -> Nullable<Text>,
+        avatar_url -> Nullable<Varchar>,
+        location -> Nullable<Varchar>,
+        website -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        is_admin -> Bool,
+        deactivated_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        email_verified_at -> Nullable<Timestamp>,
+        password_hashed_at -> Nullable<Timestamp>,
     }
 }
+
+joinable!(comments -> users (author_id));
+joinable!(conversation_members -> conversations (conversation_id));
+joinable!(conversation_members -> users (user_id));
+joinable!(email_verifications -> users (user_id));
+joinable!(messages -> conversations (conversation_id));
+joinable!(messages -> users (sender_id));
+joinable!(password_resets -> users (user_id));
+joinable!(posts -> users (author_id));
+joinable!(reactions -> posts (post_id));
+joinable!(reactions -> users (user_id));
+joinable!(sessions -> users (user_id));
+joinable!(timeline_entries -> posts (post_id));
+
+allow_tables_to_appear_in_same_query!(
+    comments,
+    conversation_members,
+    conversations,
+    email_verifications,
+    follows,
+    messages,
+    password_resets,
+    posts,
+    reactions,
+    sessions,
+    timeline_entries,
+    users,
+);
@@ -0,0 +1,362 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::result::Error;
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{path, post, Filter, Rejection};
+
+use crate::config::SessionSettings;
+use crate::db::{blocking, with_db_conn};
+use crate::error::ApiError;
+use crate::filters::{json_body, with_settings};
+use crate::session::repository::SessionRepo;
+use crate::user::model::User;
+use crate::user::password;
+use crate::user::repository::UserRepo;
+use crate::ConnectionPool;
+
+use super::model::NewSession;
+use super::{token, view};
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    path!("sessions")
+        .and(post())
+        .and(with_db_conn(pool))
+        .and(json_body())
+        .and(with_settings(settings))
+        .and_then(session_create)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RequestBody {
+    pub login: String,
+    pub password: String,
+}
+
+async fn session_create(
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: RequestBody,
+    settings: SessionSettings,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        let user = match UserRepo::find_by_login(&conn, &req.login) {
+            Ok(user) => user,
+            Err(Error::NotFound) => {
+                password::verify_dummy(&req.password);
+                return Err(ApiError::InvalidCredentials);
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        if !UserRepo::verify_password(&conn, &user, &req.password)? {
+            return Err(ApiError::InvalidCredentials);
+        }
+
+        let token = token::generate();
+        let session = SessionRepo::create(
+            &conn,
+            NewSession::new(user.id, &token, &settings),
+        )?;
+        Ok((session, token))
+    })
+    .await;
+
+    match result {
+        Ok((session, token)) => {
+            let resp = view::session_create(&session, &token);
+            Ok(with_status(json(&resp), StatusCode::CREATED))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+/// Extracts the `Authorization: Bearer <token>` header and yields the user
+/// owning that session, rejecting with `ApiError::Unauthorized` otherwise.
+pub fn with_auth(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = (User,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(with_db_conn(pool))
+        .and(with_settings(settings))
+        .and_then(authenticate)
+}
+
+/// Like `with_auth`, but when `REQUIRE_VERIFIED_EMAIL` is on, users who
+/// haven't verified their email are rejected with
+/// `ApiError::EmailNotVerified`. Guards actions that publish content.
+pub fn with_verified_auth(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = (User,), Error = Rejection> + Clone {
+    let required = settings.require_verified_email;
+    with_auth(pool, settings).and_then(move |user: User| async move {
+        if required && user.email_verified_at.is_none() {
+            Err(warp::reject::custom(ApiError::EmailNotVerified))
+        } else {
+            Ok(user)
+        }
+    })
+}
+
+/// Like `with_auth`, but anonymous requests pass through as `None`. A
+/// token that is present but invalid is still rejected.
+pub fn with_optional_auth(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+) -> impl Filter<Extract = (Option<User>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(with_db_conn(pool))
+        .and(with_settings(settings))
+        .and_then(
+            |header: Option<String>,
+             conn: PooledConnection<ConnectionManager<PgConnection>>,
+             settings: SessionSettings| async move {
+                match header {
+                    None => Ok(None),
+                    header => {
+                        authenticate(header, conn, settings).await.map(Some)
+                    }
+                }
+            },
+        )
+}
+
+async fn authenticate(
+    header: Option<String>,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    settings: SessionSettings,
+) -> Result<User, Rejection> {
+    let token = header
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| warp::reject::custom(ApiError::Unauthorized))?;
+
+    let token_hash = token::digest(&settings.secret, token.trim());
+    blocking(move || match SessionRepo::find_user(&conn, &token_hash) {
+        Err(Error::NotFound) => Err(ApiError::Unauthorized),
+        result => result.map_err(ApiError::from),
+    })
+    .await
+    .map_err(warp::reject::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use fake::faker::internet::en::{FreeEmail, Password};
+    use fake::faker::name::en::Name;
+    use fake::Fake;
+    use serde_json::json;
+    use warp::test::request;
+    use warp::Reply;
+
+    use crate::test_helpers::{establish_connection, session_settings};
+    use crate::user::model::NewUser;
+
+    use super::*;
+
+    fn invalid_credentials_body() -> String {
+        json!({
+            "error": {
+                "code": "invalid_credentials",
+                "message": "Invalid login or password",
+                "field": null
+            }
+        })
+        .to_string()
+    }
+
+    fn create_fake_user(conn: &PgConnection, password: &str) -> User {
+        let user = NewUser {
+            username: Name().fake(),
+            password: password.to_string(),
+            email: FreeEmail().fake(),
+            ..Default::default()
+        };
+        UserRepo::create(conn, user).expect("Failed to create fake user")
+    }
+
+    #[tokio::test]
+    async fn post_session_succeeds_for_valid_username_and_password() {
+        let db = establish_connection();
+        let password: String = Password(8..12).fake();
+        let bob = create_fake_user(&db.get().unwrap(), &password);
+        let req = RequestBody {
+            login: bob.username,
+            password,
+        };
+
+        let filter = routes(db.clone(), session_settings());
+        let resp = request()
+            .method("POST")
+            .path("/sessions")
+            .json(&req)
+            .reply(&filter)
+            .await;
+
+        let actual_resp_body: HashMap<String, String> =
+            std::str::from_utf8(resp.body())
+                .map(|body| serde_json::from_str(body).unwrap())
+                .expect("Invalid response");
+
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(actual_resp_body["token_type"], "Bearer");
+        assert!(actual_resp_body.contains_key("token"));
+        assert!(actual_resp_body.contains_key("expires_at"));
+    }
+
+    #[tokio::test]
+    async fn session_create_accepts_email_as_login() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn, "password");
+        let req = RequestBody {
+            login: bob.email,
+            password: "password".to_string(),
+        };
+
+        let resp = session_create(conn, req, session_settings())
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn session_create_fails_for_wrong_password() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn, "password");
+        let req = RequestBody {
+            login: bob.username,
+            password: "wrong".to_string(),
+        };
+
+        let (parts, body) = session_create(conn, req, session_settings())
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+
+        assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, invalid_credentials_body());
+    }
+
+    #[tokio::test]
+    async fn session_create_fails_for_unknown_user() {
+        let conn = establish_connection().get().unwrap();
+        let req = RequestBody {
+            login: Name().fake(),
+            password: "password".to_string(),
+        };
+
+        let (parts, body) = session_create(conn, req, session_settings())
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+
+        assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, invalid_credentials_body());
+    }
+
+    #[tokio::test]
+    async fn with_auth_yields_user_for_valid_token() {
+        let db = establish_connection();
+        let bob = create_fake_user(&db.get().unwrap(), "password");
+        SessionRepo::create(
+            &db.get().unwrap(),
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+
+        let user = request()
+            .header("authorization", "Bearer token")
+            .filter(&with_auth(db.clone(), session_settings()))
+            .await
+            .unwrap();
+
+        assert_eq!(user, bob);
+    }
+
+    #[tokio::test]
+    async fn with_verified_auth_blocks_unverified_users_only_when_required() {
+        let db = establish_connection();
+        let bob = create_fake_user(&db.get().unwrap(), "password");
+        SessionRepo::create(
+            &db.get().unwrap(),
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+        let strict = SessionSettings {
+            require_verified_email: true,
+            ..session_settings()
+        };
+
+        let user = request()
+            .header("authorization", "Bearer token")
+            .filter(&with_verified_auth(db.clone(), session_settings()))
+            .await
+            .unwrap();
+        assert_eq!(user, bob);
+
+        let rejection = request()
+            .header("authorization", "Bearer token")
+            .filter(&with_verified_auth(db.clone(), strict))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            rejection.find::<ApiError>(),
+            Some(&ApiError::EmailNotVerified)
+        );
+    }
+
+    #[tokio::test]
+    async fn with_auth_rejects_missing_or_unknown_token() {
+        let db = establish_connection();
+
+        let missing = request()
+            .filter(&with_auth(db.clone(), session_settings()))
+            .await;
+        let unknown = request()
+            .header("authorization", "Bearer unknown")
+            .filter(&with_auth(db.clone(), session_settings()))
+            .await;
+
+        assert_eq!(
+            missing.unwrap_err().find::<ApiError>(),
+            Some(&ApiError::Unauthorized)
+        );
+        assert_eq!(
+            unknown.unwrap_err().find::<ApiError>(),
+            Some(&ApiError::Unauthorized)
+        );
+    }
+
+    #[tokio::test]
+    async fn with_optional_auth_allows_anonymous_requests() {
+        let db = establish_connection();
+
+        let anonymous = request()
+            .filter(&with_optional_auth(db.clone(), session_settings()))
+            .await;
+        let unknown = request()
+            .header("authorization", "Bearer unknown")
+            .filter(&with_optional_auth(db.clone(), session_settings()))
+            .await;
+
+        assert_eq!(anonymous.unwrap(), None);
+        assert_eq!(
+            unknown.unwrap_err().find::<ApiError>(),
+            Some(&ApiError::Unauthorized)
+        );
+    }
+}
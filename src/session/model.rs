@@ -0,0 +1,61 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Insertable, Queryable};
+use uuid::Uuid;
+
+use crate::config::SessionSettings;
+use crate::schema::sessions;
+
+use super::token;
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "sessions"]
+pub struct NewSession {
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl NewSession {
+    pub fn new(user_id: Uuid, token: &str, settings: &SessionSettings) -> Self {
+        NewSession {
+            user_id,
+            token_hash: token::digest(&settings.secret, token),
+            expires_at: Utc::now().naive_utc() + settings.ttl,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::test_helpers::session_settings;
+
+    use super::*;
+
+    #[test]
+    fn new_session_stores_token_digest_and_expiry() {
+        let settings = session_settings();
+        let user_id = Uuid::new_v4();
+        let session = NewSession::new(user_id, "token", &settings);
+
+        let ttl = session.expires_at - Utc::now().naive_utc();
+
+        assert_eq!(session.user_id, user_id);
+        assert_eq!(
+            session.token_hash,
+            token::digest(&settings.secret, "token")
+        );
+        assert!(ttl > settings.ttl - Duration::minutes(1));
+        assert!(ttl <= settings.ttl);
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::QueryResult;
+use uuid::Uuid;
+
+use crate::schema::{sessions, users};
+use crate::user::model::User;
+use crate::user::repository::active;
+
+use super::model::{NewSession, Session};
+
+pub struct SessionRepo;
+
+impl SessionRepo {
+    pub fn create(
+        conn: &PgConnection,
+        new_session: NewSession,
+    ) -> QueryResult<Session> {
+        diesel::insert_into(sessions::table)
+            .values(new_session)
+            .get_result(conn)
+    }
+
+    /// Resolves a token digest to its user, ignoring expired sessions and
+    /// deactivated or deleted accounts.
+    pub fn find_user(
+        conn: &PgConnection,
+        token_hash: &str,
+    ) -> QueryResult<User> {
+        sessions::table
+            .inner_join(users::table)
+            .filter(sessions::token_hash.eq(token_hash))
+            .filter(sessions::expires_at.gt(Utc::now().naive_utc()))
+            .filter(active())
+            .select(users::all_columns)
+            .first(conn)
+    }
+
+    /// Signs the user out everywhere, returning how many sessions ended.
+    pub fn delete_for_user(
+        conn: &PgConnection,
+        user_id: Uuid,
+    ) -> QueryResult<usize> {
+        diesel::delete(sessions::table.filter(sessions::user_id.eq(user_id)))
+            .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use diesel::{PgConnection, RunQueryDsl};
+    use fake::faker::internet::en::{FreeEmail, Password};
+    use fake::faker::name::en::Name;
+    use fake::Fake;
+    use uuid::Uuid;
+
+    use crate::schema::sessions;
+    use crate::session::model::NewSession;
+    use crate::session::repository::SessionRepo;
+    use crate::session::token;
+    use crate::test_helpers::{establish_connection, session_settings};
+    use crate::user::model::{NewUser, User};
+    use crate::user::repository::UserRepo;
+
+    fn digest(token: &str) -> String {
+        token::digest(&session_settings().secret, token)
+    }
+
+    fn create_fake_user(conn: &PgConnection) -> User {
+        let user = NewUser {
+            username: Name().fake(),
+            password: Password(5..10).fake(),
+            email: FreeEmail().fake(),
+            ..Default::default()
+        };
+        UserRepo::create(conn, user).expect("Failed to create fake user")
+    }
+
+    #[test]
+    fn creates_session_for_existing_user() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn);
+
+        let session = SessionRepo::create(
+            &conn,
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+
+        assert_eq!(session.user_id, bob.id);
+        assert_eq!(session.token_hash, digest("token"));
+    }
+
+    #[test]
+    fn create_returns_error_for_non_existent_user() {
+        let conn = establish_connection().get().unwrap();
+        let new_session =
+            NewSession::new(Uuid::new_v4(), "token", &session_settings());
+
+        let result = SessionRepo::create(&conn, new_session);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn find_user_returns_owner_of_valid_token() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn);
+        SessionRepo::create(
+            &conn,
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+
+        assert_eq!(SessionRepo::find_user(&conn, &digest("token")), Ok(bob));
+        assert!(SessionRepo::find_user(&conn, &digest("other")).is_err());
+    }
+
+    #[test]
+    fn find_user_ignores_expired_sessions() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn);
+        let expired = NewSession {
+            expires_at: Utc::now().naive_utc() - Duration::minutes(1),
+            ..NewSession::new(bob.id, "token", &session_settings())
+        };
+        diesel::insert_into(sessions::table)
+            .values(expired)
+            .execute(&conn)
+            .unwrap();
+
+        assert!(SessionRepo::find_user(&conn, &digest("token")).is_err());
+    }
+
+    #[test]
+    fn find_user_ignores_deactivated_accounts() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn);
+        SessionRepo::create(
+            &conn,
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+
+        UserRepo::deactivate(&conn, bob.id).unwrap();
+        assert!(SessionRepo::find_user(&conn, &digest("token")).is_err());
+    }
+
+    #[test]
+    fn delete_for_user_ends_every_session() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_user(&conn);
+        for token in &["one", "two"] {
+            SessionRepo::create(
+                &conn,
+                NewSession::new(bob.id, token, &session_settings()),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(SessionRepo::delete_for_user(&conn, bob.id), Ok(2));
+        assert!(SessionRepo::find_user(&conn, &digest("one")).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+const TOKEN_BYTES: usize = 32;
+
+pub fn generate() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Only a digest keyed with the session secret is persisted, so a leaked
+/// `sessions` table can't be replayed as bearer tokens.
+pub fn digest(secret: &str, token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(secret.as_bytes());
+    hasher.update(b":");
+    hasher.update(token.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_returns_unique_hex_tokens() {
+        let first = generate();
+        let second = generate();
+
+        assert_eq!(first.len(), TOKEN_BYTES * 2);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn digest_is_stable_and_differs_from_token() {
+        let token = generate();
+
+        assert_eq!(digest("secret", &token), digest("secret", &token));
+        assert_ne!(digest("secret", &token), token);
+    }
+
+    #[test]
+    fn digest_depends_on_secret() {
+        assert_ne!(digest("secret", "token"), digest("other", "token"));
+    }
+}
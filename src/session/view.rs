@@ -0,0 +1,39 @@
+use serde_json::{json, Value};
+
+use super::model::Session;
+
+pub fn session_create(session: &Session, token: &str) -> Value {
+    json!({
+        "token": token,
+        "token_type": "Bearer",
+        "expires_at": session.expires_at
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn session_create_view_returns_token_and_expiry() {
+        let session = Session {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            token_hash: "digest".to_string(),
+            created_at: Utc::now().naive_utc(),
+            expires_at: Utc::now().naive_utc(),
+        };
+
+        let expected = json!({
+            "token": "token",
+            "token_type": "Bearer",
+            "expires_at": session.expires_at
+        });
+
+        assert_eq!(session_create(&session, "token"), expected);
+    }
+}
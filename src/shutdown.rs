@@ -0,0 +1,171 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Semaphore;
+use tokio::task;
+
+/// Shared flag flipped once shutdown starts, so health checks can fail and
+/// load balancers stop routing new traffic to this instance.
+#[derive(Clone, Default, Debug)]
+pub struct Shutdown {
+    draining: Arc<AtomicBool>,
+}
+
+impl Shutdown {
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Tracks work handed off after a response was sent, such as outgoing
+/// emails, so shutdown can wait for it instead of cutting it off.
+#[derive(Clone, Default, Debug)]
+pub struct Tasks {
+    running: Arc<AtomicUsize>,
+}
+
+impl Tasks {
+    /// Runs `job` on the blocking pool once it holds a permit from `limit`,
+    /// so callers cap how many of their jobs run at once. Jobs still waiting
+    /// for a permit count as running.
+    pub fn spawn_blocking<F>(&self, limit: Arc<Semaphore>, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.running.fetch_add(1, Ordering::SeqCst);
+        let running = RunningGuard(self.running.clone());
+        task::spawn(async move {
+            let _running = running;
+            let _permit = limit.acquire_owned().await;
+            if let Err(err) = task::spawn_blocking(job).await {
+                error!("Background job failed: {}", err);
+            }
+        });
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once every tracked job has finished.
+    pub async fn finished(&self) {
+        while self.running() > 0 {
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+        }
+    }
+}
+
+/// Counts a job as finished even if it panics.
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Resolves on the first SIGTERM or SIGINT.
+pub async fn signal_received() {
+    let mut terminate = signal(SignalKind::terminate())
+        .expect("Couldn't install SIGTERM handler");
+    let mut interrupt = signal(SignalKind::interrupt())
+        .expect("Couldn't install SIGINT handler");
+
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM"),
+        _ = interrupt.recv() => info!("Received SIGINT"),
+    }
+}
+
+/// Waits for the server to finish in-flight requests, giving up after
+/// `deadline`. Returns whether every request completed in time.
+pub async fn drain<F: Future>(server: F, deadline: Duration) -> bool {
+    tokio::time::timeout(deadline, server).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[test]
+    fn shutdown_flag_is_shared_between_clones() {
+        let shutdown = Shutdown::default();
+        let handle = shutdown.clone();
+
+        assert!(!handle.is_draining());
+        shutdown.start_draining();
+        assert!(handle.is_draining());
+    }
+
+    #[tokio::test]
+    async fn tasks_finish_once_every_job_has_run() {
+        let tasks = Tasks::default();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        tasks.spawn_blocking(Arc::new(Semaphore::new(1)), move || {
+            released.recv().ok();
+        });
+        assert_eq!(tasks.running(), 1);
+        assert!(!drain(tasks.finished(), Duration::from_millis(50)).await);
+
+        release.send(()).unwrap();
+        assert!(drain(tasks.finished(), Duration::from_secs(1)).await);
+        assert_eq!(tasks.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn limited_jobs_wait_for_a_permit() {
+        let tasks = Tasks::default();
+        let limit = Arc::new(Semaphore::new(1));
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = Arc::new(std::sync::Mutex::new(released));
+        let (started, mut starts) = tokio::sync::mpsc::unbounded_channel();
+        for job in 0..2 {
+            let (released, started) = (released.clone(), started.clone());
+            tasks.spawn_blocking(limit.clone(), move || {
+                started.send(job).unwrap();
+                released.lock().unwrap().recv().ok();
+            });
+        }
+
+        assert_eq!(
+            timeout(Duration::from_secs(1), starts.recv()).await,
+            Ok(Some(0))
+        );
+        assert!(timeout(Duration::from_millis(50), starts.recv())
+            .await
+            .is_err());
+        assert_eq!(tasks.running(), 2);
+
+        release.send(()).unwrap();
+        assert_eq!(
+            timeout(Duration::from_secs(1), starts.recv()).await,
+            Ok(Some(1))
+        );
+        release.send(()).unwrap();
+        assert!(drain(tasks.finished(), Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn drain_reports_whether_server_finished_before_deadline() {
+        let finished = drain(
+            tokio::time::delay_for(Duration::from_millis(10)),
+            Duration::from_secs(1),
+        )
+        .await;
+        let stuck =
+            drain(std::future::pending::<()>(), Duration::from_millis(10))
+                .await;
+
+        assert!(finished);
+        assert!(!stuck);
+    }
+}
@@ -1,171 +1,446 @@
 use std::convert::Infallible;
 
 use diesel::r2d2::{ConnectionManager, PooledConnection};
-use diesel::PgConnection;
+use diesel::{Connection, PgConnection, QueryResult};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use warp::http::StatusCode;
 use warp::reply::{json, with_status, Json, WithStatus};
-use warp::{delete, get, path, post, Filter, Rejection};
+use warp::{delete, get, patch, path, post, Filter};
 
+use crate::config::{AccountSettings, SessionSettings};
+use crate::db::{blocking, with_db_conn};
+use crate::error::ApiError;
+use crate::filters::{json_body, with_account, with_settings};
+use crate::follow::model::FollowCounts;
+use crate::follow::repository::FollowRepo;
+use crate::mailer::{with_mailer, SharedMailer};
+use crate::pagination::Cursor;
+use crate::session::handler::{with_auth, with_optional_auth};
+use crate::session::repository::SessionRepo;
 use crate::user::repository::UserRepo;
+use crate::verification::handler::issue_verification;
 use crate::ConnectionPool;
 
-use super::model::NewUser;
-use super::view;
+use super::model::{ListParams, NewUser, SortOrder, User, Visibility};
+use super::{password, validation, view};
 
 pub fn routes(
     pool: ConnectionPool,
+    settings: SessionSettings,
+    account: AccountSettings,
+    mailer: SharedMailer,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     let user_index_route = path!("users")
         .and(get())
+        .and(with_optional_auth(pool.clone(), settings.clone()))
+        .and(warp::query::<IndexQuery>())
         .and(with_db_conn(pool.clone()))
         .and_then(user_index);
 
     let user_details_route = path!("users" / Uuid)
         .and(get())
+        .and(with_optional_auth(pool.clone(), settings.clone()))
         .and(with_db_conn(pool.clone()))
         .and_then(user_details);
 
+    let user_by_username_route = path!("users" / "by-username" / String)
+        .and(get())
+        .and(with_optional_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and_then(user_by_username);
+
     let user_create_route = path!("users")
         .and(post())
         .and(with_db_conn(pool.clone()))
         .and(json_body())
+        .and(with_settings(settings.clone()))
+        .and(with_account(account.clone()))
+        .and(with_mailer(mailer))
         .and_then(user_create);
 
+    let user_update_route = path!("users" / Uuid)
+        .and(patch())
+        .and(with_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(json_body())
+        .and_then(user_update);
+
     let user_delete_route = path!("users" / Uuid)
         .and(delete())
-        .and(with_db_conn(pool))
+        .and(with_auth(pool.clone(), settings.clone()))
+        .and(with_account(account.clone()))
+        .and(with_db_conn(pool.clone()))
         .and_then(user_delete);
 
+    let user_deactivate_route = path!("users" / Uuid / "deactivate")
+        .and(post())
+        .and(with_auth(pool.clone(), settings))
+        .and(with_db_conn(pool.clone()))
+        .and_then(user_deactivate);
+
+    let user_restore_route = path!("users" / "restore")
+        .and(post())
+        .and(with_account(account))
+        .and(with_db_conn(pool))
+        .and(json_body())
+        .and_then(user_restore);
+
     user_index_route
         .or(user_details_route)
+        .or(user_by_username_route)
         .or(user_create_route)
+        .or(user_update_route)
         .or(user_delete_route)
+        .or(user_deactivate_route)
+        .or(user_restore_route)
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default, Debug)]
 pub struct RequestBody {
     pub username: String,
     pub password: String,
     pub email: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RestoreRequestBody {
+    pub login: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct IndexQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<Cursor>,
+    pub order: Option<SortOrder>,
+    pub username_prefix: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct UpdateRequestBody {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub current_password: Option<String>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
 }
 
 async fn user_index(
+    viewer: Option<User>,
+    query: IndexQuery,
     conn: PooledConnection<ConnectionManager<PgConnection>>,
-) -> Result<Json, Infallible> {
-    let users = UserRepo::read_all(&conn);
-    let resp = view::user_list(&users);
-    Ok(json(&resp))
+) -> Result<WithStatus<Json>, Infallible> {
+    let params: ListParams = query.into();
+    match blocking(move || UserRepo::list(&conn, &params)).await {
+        Ok((users, next_cursor)) => {
+            let resp = view::user_page(&users, next_cursor, viewer.as_ref());
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
 }
 
+/// New accounts are active straight away; the verification email is best
+/// effort and can be requested again from `POST /email-verifications`.
 async fn user_create(
     conn: PooledConnection<ConnectionManager<PgConnection>>,
     req: RequestBody,
+    settings: SessionSettings,
+    account: AccountSettings,
+    mailer: SharedMailer,
 ) -> Result<WithStatus<Json>, Infallible> {
+    let req = match validation::validate_new_user(req) {
+        Ok(req) => req,
+        Err(err) => return Ok(err.reply()),
+    };
+
     let new_user: NewUser = req.into();
-    let result = UserRepo::create(&conn, new_user);
+    let result = blocking(move || {
+        let user = UserRepo::create(&conn, new_user)?;
+        let email = issue_verification(&conn, &user, &settings, &account);
+        Ok::<_, ApiError>((user, email))
+    })
+    .await;
 
     match result {
-        Ok(user) => {
+        Ok((user, email)) => {
+            let sent = match email {
+                Ok(email) => blocking(move || mailer.send(&email)).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = sent {
+                warn!(
+                    "Couldn't send verification email to user {}: {}",
+                    user.id,
+                    err.message()
+                );
+            }
+
             let resp = view::user_create(&user);
             Ok(with_status(json(&resp), StatusCode::CREATED))
         }
-        Err(err) => Ok(with_status(
-            json(&err.to_string()),
-            StatusCode::UNPROCESSABLE_ENTITY,
-        )),
+        Err(err) => Ok(err.reply()),
     }
 }
 
 async fn user_details(
     id: Uuid,
+    viewer: Option<User>,
     conn: PooledConnection<ConnectionManager<PgConnection>>,
 ) -> Result<WithStatus<Json>, Infallible> {
-    match UserRepo::find(&conn, id) {
-        Ok(user) => {
-            let resp = view::user_details(&user);
+    let result = blocking(move || {
+        let user = UserRepo::find(&conn, id)?;
+        let (counts, visibility) = profile_for(&conn, &user, viewer.as_ref())?;
+        Ok::<_, diesel::result::Error>((user, counts, visibility))
+    })
+    .await;
+
+    match result {
+        Ok((user, counts, visibility)) => {
+            let resp = view::user_details(&user, &counts, visibility);
             Ok(with_status(json(&resp), StatusCode::OK))
         }
-        Err(err) => {
-            Ok(with_status(json(&err.to_string()), StatusCode::NOT_FOUND))
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn user_by_username(
+    username: String,
+    viewer: Option<User>,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        let user = UserRepo::find_by_username(&conn, &username)?;
+        let (counts, visibility) = profile_for(&conn, &user, viewer.as_ref())?;
+        Ok::<_, diesel::result::Error>((user, counts, visibility))
+    })
+    .await;
+
+    match result {
+        Ok((user, counts, visibility)) => {
+            let resp = view::user_details(&user, &counts, visibility);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+/// Follow counts for `user` and how much of the profile `viewer` may see.
+fn profile_for(
+    conn: &PgConnection,
+    user: &User,
+    viewer: Option<&User>,
+) -> QueryResult<(FollowCounts, Visibility)> {
+    let counts = FollowRepo::counts(conn, user.id)?;
+    let follows = match viewer {
+        Some(viewer) if viewer.id != user.id => {
+            FollowRepo::is_following(conn, viewer.id, user.id)?
+        }
+        _ => false,
+    };
+    Ok((counts, Visibility::of(viewer, user, follows)))
+}
+
+async fn user_update(
+    id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: UpdateRequestBody,
+) -> Result<WithStatus<Json>, Infallible> {
+    if current_user.id != id {
+        return Ok(ApiError::Forbidden.reply());
+    }
+
+    let req = match validation::validate_update(req) {
+        Ok(req) => req,
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let result = blocking(move || {
+        if req.password.is_some() {
+            let current_password =
+                req.current_password.as_deref().unwrap_or("");
+            if !UserRepo::verify_password(
+                &conn,
+                &current_user,
+                current_password,
+            )? {
+                return Err(ApiError::IncorrectPassword);
+            }
+        }
+        let user = UserRepo::update(&conn, id, req.into())?;
+        let (counts, visibility) =
+            profile_for(&conn, &user, Some(&current_user))?;
+        Ok((user, counts, visibility))
+    })
+    .await;
+
+    match result {
+        Ok((user, counts, visibility)) => {
+            let resp = view::user_details(&user, &counts, visibility);
+            Ok(with_status(json(&resp), StatusCode::OK))
         }
+        Err(err) => Ok(err.reply()),
     }
 }
 
+/// Hides the account and signs it out everywhere; the row is purged once
+/// the grace period has passed unless the owner restores it first.
 async fn user_delete(
     id: Uuid,
+    current_user: User,
+    account: AccountSettings,
     conn: PooledConnection<ConnectionManager<PgConnection>>,
 ) -> Result<WithStatus<Json>, Infallible> {
-    match UserRepo::delete(&conn, id) {
-        Ok(val) if val > 0 => Ok(with_status(
-            json(&"{\"success\": true}".to_string()),
-            StatusCode::OK,
-        )),
-        Ok(_) => Ok(with_status(
-            json(&"{\"success\": false}".to_string()),
-            StatusCode::NOT_FOUND,
-        )),
-        Err(err) => {
-            error!("Something went really wrong while deleting user");
-            Ok(with_status(
-                json(&err.to_string()),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ))
+    if current_user.id != id {
+        return Ok(ApiError::Forbidden.reply());
+    }
+
+    let result = blocking(move || {
+        conn.transaction(|| {
+            let user = UserRepo::schedule_deletion(&conn, id)?;
+            SessionRepo::delete_for_user(&conn, id)?;
+            Ok::<_, diesel::result::Error>(user)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(user) => {
+            let resp = view::user_deletion(&user, account.deletion_grace);
+            Ok(with_status(json(&resp), StatusCode::ACCEPTED))
         }
+        Err(err) => Ok(err.reply()),
     }
 }
 
-fn with_db_conn(
-    pool: ConnectionPool,
-) -> impl Filter<
-    Extract = (PooledConnection<ConnectionManager<PgConnection>>,),
-    Error = Infallible,
-> + Clone {
-    warp::any().map(move || pool.get().unwrap())
+async fn user_deactivate(
+    id: Uuid,
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+) -> Result<WithStatus<Json>, Infallible> {
+    if current_user.id != id {
+        return Ok(ApiError::Forbidden.reply());
+    }
+
+    let result = blocking(move || {
+        conn.transaction(|| {
+            let user = UserRepo::deactivate(&conn, id)?;
+            SessionRepo::delete_for_user(&conn, id)?;
+            Ok::<_, diesel::result::Error>(user)
+        })
+    })
+    .await;
+
+    match result {
+        Ok(user) => {
+            let resp = view::user_deactivation(&user);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
 }
 
-fn json_body(
-) -> impl Filter<Extract = (RequestBody,), Error = Rejection> + Clone {
-    warp::body::json()
+/// Reactivates a deactivated account, or cancels a pending deletion, for
+/// whoever can still prove they own it.
+async fn user_restore(
+    account: AccountSettings,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    req: RestoreRequestBody,
+) -> Result<WithStatus<Json>, Infallible> {
+    let result = blocking(move || {
+        let user = match UserRepo::find_restorable(
+            &conn,
+            &req.login,
+            account.deletion_grace,
+        ) {
+            Ok(user) => user,
+            Err(diesel::result::Error::NotFound) => {
+                password::verify_dummy(&req.password);
+                return Err(ApiError::InvalidCredentials);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if !UserRepo::verify_password(&conn, &user, &req.password)? {
+            return Err(ApiError::InvalidCredentials);
+        }
+
+        let user = UserRepo::restore(&conn, user.id)?;
+        let (counts, visibility) = profile_for(&conn, &user, Some(&user))?;
+        Ok((user, counts, visibility))
+    })
+    .await;
+
+    match result {
+        Ok((user, counts, visibility)) => {
+            let resp = view::user_details(&user, &counts, visibility);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::sync::Arc;
 
-    use diesel::RunQueryDsl;
-    use fake::faker::internet::en::{FreeEmail, Password};
-    use fake::faker::name::en::Name;
+    use diesel::{QueryDsl, RunQueryDsl};
+    use fake::faker::internet::en::{FreeEmail, Password, Username};
     use fake::Fake;
     use serde_json::json;
     use warp::http::StatusCode;
     use warp::test::request;
     use warp::Reply;
 
+    use crate::error::handle_rejection;
+    use crate::follow::model::NewFollow;
+    use crate::mailer::memory::MemoryMailer;
     use crate::schema::users;
-    use crate::test_helpers::establish_connection;
-    use crate::user::model::User;
+    use crate::session::model::NewSession;
+    use crate::test_helpers::{
+        account_settings, establish_connection, session_settings,
+        unreachable_pool, PoolProbe,
+    };
 
     use super::*;
 
+    fn mailer() -> SharedMailer {
+        Arc::new(MemoryMailer::default())
+    }
+
     fn create_fake_users(conn: &PgConnection) -> User {
         let user = NewUser {
-            username: Name().fake(),
+            username: Username().fake(),
             password: Password(5..10).fake(),
             email: FreeEmail().fake(),
+            ..Default::default()
         };
-        let user = diesel::insert_into(users::table)
+        diesel::insert_into(users::table)
             .values(&user)
             .get_result(conn)
-            .expect("Failed to create fake user");
-        user
+            .expect("Failed to create fake user")
     }
 
     #[tokio::test]
     async fn test_user_index() {
         let db = establish_connection();
-        let filter = routes(db.clone());
+        let filter = routes(
+            db.clone(),
+            session_settings(),
+            account_settings(),
+            mailer(),
+        );
         let resp = request().method("GET").path("/users").reply(&filter).await;
 
         assert_eq!(resp.status(), StatusCode::OK);
@@ -175,12 +450,19 @@ mod tests {
     async fn post_user_succeeds_for_valid_values() {
         let db = establish_connection();
         let req = RequestBody {
-            username: Name().fake(),
+            username: Username().fake(),
             email: FreeEmail().fake(),
-            password: Password(5..10).fake(),
+            password: "secret-42".to_string(),
+            ..Default::default()
         };
 
-        let filter = routes(db.clone());
+        let mailer = Arc::new(MemoryMailer::default());
+        let filter = routes(
+            db.clone(),
+            session_settings(),
+            account_settings(),
+            mailer.clone(),
+        );
         let resp = request()
             .method("POST")
             .path("/users")
@@ -196,6 +478,35 @@ mod tests {
         assert_eq!(resp.status(), StatusCode::CREATED);
         assert!(actual_resp_body.contains_key("id"));
         assert_eq!(actual_resp_body.keys().len(), 1);
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, req.email.to_lowercase());
+        assert!(sent[0].body.contains("/verify-email?token="));
+    }
+
+    #[tokio::test]
+    async fn user_create_sends_verification_after_releasing_connection() {
+        let pool = establish_connection();
+        let probe = Arc::new(PoolProbe::new(&pool));
+        let req = RequestBody {
+            username: Username().fake(),
+            password: "secret-42".to_string(),
+            email: FreeEmail().fake(),
+            ..Default::default()
+        };
+
+        let resp = user_create(
+            pool.get().unwrap(),
+            req,
+            session_settings(),
+            account_settings(),
+            probe.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(resp.into_response().status(), StatusCode::CREATED);
+        assert_eq!(probe.in_use(), vec![0]);
     }
 
     #[tokio::test]
@@ -204,111 +515,579 @@ mod tests {
         let user = create_fake_users(&conn);
         let new_user_request = RequestBody {
             username: user.username,
-            password: user.password,
-            email: user.email,
+            password: "secret-42".to_string(),
+            email: FreeEmail().fake(),
+            ..Default::default()
         };
 
-        let (parts, body) = user_create(conn, new_user_request)
-            .await
-            .unwrap()
-            .into_response()
-            .into_parts();
+        let (parts, body) = user_create(
+            conn,
+            new_user_request,
+            session_settings(),
+            account_settings(),
+            mailer(),
+        )
+        .await
+        .unwrap()
+        .into_response()
+        .into_parts();
         let body = hyper::body::to_bytes(body).await.unwrap();
+        let expected = json!({
+            "error": {
+                "code": "conflict",
+                "message": "username is already taken",
+                "field": "username"
+            }
+        })
+        .to_string();
+        assert_eq!(parts.status, StatusCode::CONFLICT);
+        assert_eq!(body, expected)
+    }
+
+    #[tokio::test]
+    async fn user_create_rejects_invalid_payload_with_field_errors() {
+        let conn = establish_connection().get().unwrap();
+        let req = RequestBody {
+            username: "b".to_string(),
+            password: "secret-42".to_string(),
+            email: "not-an-email".to_string(),
+            ..Default::default()
+        };
+
+        let (parts, body) = user_create(
+            conn,
+            req,
+            session_settings(),
+            account_settings(),
+            mailer(),
+        )
+        .await
+        .unwrap()
+        .into_response()
+        .into_parts();
+        let body: serde_json::Value =
+            serde_json::from_slice(&hyper::body::to_bytes(body).await.unwrap())
+                .unwrap();
+
         assert_eq!(parts.status, StatusCode::UNPROCESSABLE_ENTITY);
-        assert_eq!(body, "\"duplicate key value violates unique constraint \\\"users_username_key\\\"\"")
+        assert_eq!(body["error"]["code"], "validation_failed");
+        assert_eq!(body["error"]["details"][0]["field"], "username");
+        assert_eq!(body["error"]["details"][1]["field"], "email");
     }
 
     #[tokio::test]
-    async fn user_index_returns_json_array() {
+    async fn user_index_hides_emails_from_anonymous_viewers() {
         let pool = establish_connection();
         let conn = pool.get().unwrap();
 
-        let bob = create_fake_users(&conn);
-        let alice = create_fake_users(&conn);
-        let expected = json!([
-            {
-                "email": bob.email,
-                "id": bob.id,
-                "username": bob.username
-            },{
-                "email": alice.email,
-                "id": alice.id,
-                "username": alice.username
-            }
-        ])
-        .to_string();
+        let mut users = [create_fake_users(&conn), create_fake_users(&conn)];
+        users.sort_by_key(|user| (user.created_at, user.id));
+        let (bob, alice) = (&users[0], &users[1]);
+        let expected = json!({
+            "data": [
+                {
+                    "id": bob.id,
+                    "username": bob.username,
+                    "display_name": null,
+                    "avatar_url": null
+                },{
+                    "id": alice.id,
+                    "username": alice.username,
+                    "display_name": null,
+                    "avatar_url": null
+                }
+            ],
+            "next_cursor": null
+        });
 
-        let result = user_index(conn).await.unwrap().into_response();
-        let actual = hyper::body::to_bytes(result.into_body()).await.unwrap();
+        let result = user_index(None, IndexQuery::default(), conn)
+            .await
+            .unwrap()
+            .into_response();
+        let body = hyper::body::to_bytes(result.into_body()).await.unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(actual, expected)
     }
 
     #[tokio::test]
-    async fn user_details_returns_user_json_value() {
+    async fn user_index_returns_next_cursor_when_more_users_exist() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+
+        let mut users = [create_fake_users(&conn), create_fake_users(&conn)];
+        users.sort_by_key(|user| (user.created_at, user.id));
+        let query = IndexQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+
+        let result =
+            user_index(None, query, conn).await.unwrap().into_response();
+        let body = hyper::body::to_bytes(result.into_body()).await.unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(actual["data"].as_array().unwrap().len(), 1);
+        assert_eq!(actual["data"][0]["id"], json!(users[0].id));
+        let cursor = Cursor::new(users[0].created_at, users[0].id);
+        assert_eq!(actual["next_cursor"], json!(cursor.to_string()));
+    }
+
+    #[tokio::test]
+    async fn user_index_resumes_from_cursor_in_query() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let mut users = [create_fake_users(&conn), create_fake_users(&conn)];
+        users.sort_by_key(|user| (user.created_at, user.id));
+        drop(conn);
+        let filter =
+            routes(pool, session_settings(), account_settings(), mailer())
+                .recover(handle_rejection);
+
+        let resp = request().path("/users?limit=1").reply(&filter).await;
+        let first: serde_json::Value =
+            serde_json::from_slice(resp.body()).unwrap();
+        let path = format!(
+            "/users?limit=1&cursor={}",
+            first["next_cursor"].as_str().unwrap()
+        );
+        let resp = request().path(&path).reply(&filter).await;
+        let second: serde_json::Value =
+            serde_json::from_slice(resp.body()).unwrap();
+
+        assert_eq!(first["data"][0]["id"], json!(users[0].id));
+        assert_eq!(second["data"][0]["id"], json!(users[1].id));
+        assert_eq!(second["next_cursor"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn user_index_rejects_malformed_query() {
+        let db = establish_connection();
+        let filter = routes(
+            db.clone(),
+            session_settings(),
+            account_settings(),
+            mailer(),
+        )
+        .recover(handle_rejection);
+        let resp = request()
+            .method("GET")
+            .path("/users?cursor=not-a-cursor")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn user_details_returns_public_profile_to_anonymous_viewers() {
         let pool = establish_connection();
         let conn = pool.get().unwrap();
         let bob = create_fake_users(&conn);
-        let bob_id = bob.clone().id;
         let expected = json!({
-            "id": bob_id,
-            "username": bob.clone().username,
-            "password": "*****",
-            "email": bob.clone().email
-        })
-        .to_string();
+            "id": bob.id,
+            "username": bob.username,
+            "display_name": null,
+            "bio": null,
+            "avatar_url": null,
+            "website": null,
+            "created_at": bob.created_at,
+            "followers_count": 0,
+            "following_count": 0
+        });
 
-        let user_details =
-            user_details(bob_id, conn).await.unwrap().into_response();
-        let actual = hyper::body::to_bytes(user_details.into_body())
+        let resp = user_details(bob.id, None, conn)
             .await
-            .unwrap();
+            .unwrap()
+            .into_response();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&body).unwrap();
 
         assert_eq!(actual, expected);
     }
 
+    #[tokio::test]
+    async fn user_details_shows_private_fields_to_owner_and_location_to_followers(
+    ) {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = create_fake_users(&conn);
+        FollowRepo::follow(
+            &conn,
+            NewFollow {
+                follower_id: alice.id,
+                followee_id: bob.id,
+            },
+        )
+        .unwrap();
+        drop(conn);
+
+        let resp = user_details(bob.id, Some(bob.clone()), pool.get().unwrap())
+            .await
+            .unwrap()
+            .into_response();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let owner: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(owner["email"], json!(bob.email));
+        assert!(owner.get("password").is_none());
+
+        let resp = user_details(bob.id, Some(alice), pool.get().unwrap())
+            .await
+            .unwrap()
+            .into_response();
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        let follower: serde_json::Value =
+            serde_json::from_slice(&body).unwrap();
+        assert_eq!(follower["location"], serde_json::Value::Null);
+        assert!(follower.get("email").is_none());
+    }
+
     #[tokio::test]
     async fn user_details_returns_error_for_non_existent_user() {
         let conn = establish_connection().get().unwrap();
         let uuid = Uuid::new_v4();
-        let (parts, body) = user_details(uuid, conn)
+        let (parts, body) = user_details(uuid, None, conn)
             .await
             .unwrap()
             .into_response()
             .into_parts();
         let body = hyper::body::to_bytes(body).await.unwrap();
 
+        let expected = json!({
+            "error": {
+                "code": "not_found",
+                "message": "Resource not found",
+                "field": null
+            }
+        })
+        .to_string();
+
         assert_eq!(parts.status, StatusCode::NOT_FOUND);
-        assert_eq!(body, "\"NotFound\"");
+        assert_eq!(body, expected);
     }
 
     #[tokio::test]
-    async fn delete_returns_success_message_if_user_exist() {
+    async fn user_update_changes_username_email_and_profile() {
         let conn = establish_connection().get().unwrap();
         let bob = create_fake_users(&conn);
-        let (parts, body) = user_delete(bob.id, conn)
+        let req = UpdateRequestBody {
+            username: Some("bobby".to_string()),
+            email: Some("bobby@open.org".to_string()),
+            display_name: Some(" Bobby ".to_string()),
+            ..Default::default()
+        };
+
+        let (parts, body) = user_update(bob.id, bob.clone(), conn, req)
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+        let body: serde_json::Value =
+            serde_json::from_slice(&hyper::body::to_bytes(body).await.unwrap())
+                .unwrap();
+
+        assert_eq!(parts.status, StatusCode::OK);
+        assert_eq!(body["id"], json!(bob.id));
+        assert_eq!(body["username"], "bobby");
+        assert_eq!(body["email"], "bobby@open.org");
+        assert_eq!(body["display_name"], "Bobby");
+        assert_eq!(body["bio"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn user_by_username_ignores_case() {
+        let pool = establish_connection();
+        let bob = create_fake_users(&pool.get().unwrap());
+        let filter =
+            routes(pool, session_settings(), account_settings(), mailer());
+
+        let resp = request()
+            .method("GET")
+            .path(&format!(
+                "/users/by-username/{}",
+                bob.username.to_uppercase()
+            ))
+            .reply(&filter)
+            .await;
+        let body: serde_json::Value =
+            serde_json::from_slice(resp.body()).unwrap();
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body["id"], json!(bob.id));
+    }
+
+    #[tokio::test]
+    async fn user_update_returns_conflict_for_taken_email() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = create_fake_users(&conn);
+        let req = UpdateRequestBody {
+            email: Some(alice.email),
+            ..Default::default()
+        };
+
+        let (parts, _) = user_update(bob.id, bob, conn, req)
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+
+        assert_eq!(parts.status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn user_update_requires_current_password_for_password_change() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let req = UpdateRequestBody {
+            password: Some("new-password".to_string()),
+            current_password: Some("wrong".to_string()),
+            ..Default::default()
+        };
+
+        let (parts, body) = user_update(bob.id, bob, conn, req)
             .await
             .unwrap()
             .into_response()
             .into_parts();
         let body = hyper::body::to_bytes(body).await.unwrap();
 
+        let expected = json!({
+            "error": {
+                "code": "incorrect_password",
+                "message": "Current password is incorrect",
+                "field": "current_password"
+            }
+        })
+        .to_string();
+
+        assert_eq!(parts.status, StatusCode::FORBIDDEN);
+        assert_eq!(body, expected);
+    }
+
+    #[tokio::test]
+    async fn user_update_changes_password_with_current_password() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_fake_users(&conn);
+        let req = UpdateRequestBody {
+            password: Some("new-password".to_string()),
+            current_password: Some(bob.password.clone()),
+            ..Default::default()
+        };
+
+        let (parts, _) = user_update(bob.id, bob.clone(), conn, req)
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+
+        let conn = pool.get().unwrap();
+        let bob = UserRepo::find(&conn, bob.id).unwrap();
         assert_eq!(parts.status, StatusCode::OK);
-        assert_eq!(body, "\"{\\\"success\\\": true}\"");
+        assert_eq!(
+            UserRepo::verify_password(&conn, &bob, "new-password"),
+            Ok(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn user_update_forbids_updating_another_user() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = create_fake_users(&conn);
+        let req = UpdateRequestBody {
+            username: Some("bobby".to_string()),
+            ..Default::default()
+        };
+
+        let (parts, _) = user_update(alice.id, bob, conn, req)
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+
+        assert_eq!(parts.status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn delete_schedules_purge_and_signs_user_out() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_fake_users(&conn);
+        SessionRepo::create(
+            &conn,
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+
+        let (parts, body) =
+            user_delete(bob.id, bob.clone(), account_settings(), conn)
+                .await
+                .unwrap()
+                .into_response()
+                .into_parts();
+        let body: serde_json::Value =
+            serde_json::from_slice(&hyper::body::to_bytes(body).await.unwrap())
+                .unwrap();
+
+        assert_eq!(parts.status, StatusCode::ACCEPTED);
+        assert_eq!(body["id"], json!(bob.id));
+        let deleted_at: chrono::NaiveDateTime =
+            serde_json::from_value(body["deleted_at"].clone()).unwrap();
+        assert_eq!(
+            body["purge_after"],
+            json!(deleted_at + chrono::Duration::days(30))
+        );
+
+        let conn = pool.get().unwrap();
+        assert!(UserRepo::find(&conn, bob.id).is_err());
+        assert_eq!(SessionRepo::delete_for_user(&conn, bob.id), Ok(0));
     }
 
     #[tokio::test]
     async fn delete_returns_failure_if_user_does_not_exist() {
         let conn = establish_connection().get().unwrap();
-        let id = Uuid::new_v4();
-        let (parts, body) = user_delete(id, conn)
+        let bob = create_fake_users(&conn);
+        diesel::delete(users::table.find(bob.id))
+            .execute(&conn)
+            .unwrap();
+        let (parts, body) = user_delete(bob.id, bob, account_settings(), conn)
             .await
             .unwrap()
             .into_response()
             .into_parts();
         let body = hyper::body::to_bytes(body).await.unwrap();
 
+        let expected = json!({
+            "error": {
+                "code": "not_found",
+                "message": "Resource not found",
+                "field": null
+            }
+        })
+        .to_string();
+
         assert_eq!(parts.status, StatusCode::NOT_FOUND);
-        assert_eq!(body, "\"{\\\"success\\\": false}\"");
+        assert_eq!(body, expected);
+    }
+
+    #[tokio::test]
+    async fn delete_forbids_deleting_another_user() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = create_fake_users(&conn);
+        let (parts, _) = user_delete(alice.id, bob, account_settings(), conn)
+            .await
+            .unwrap()
+            .into_response()
+            .into_parts();
+
+        assert_eq!(parts.status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn deactivated_user_can_restore_with_password() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = UserRepo::create(
+            &conn,
+            NewUser {
+                username: Username().fake(),
+                password: "secret-42".to_string(),
+                email: FreeEmail().fake(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let resp = user_deactivate(bob.id, bob.clone(), conn).await.unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::OK);
+        assert!(UserRepo::find(&pool.get().unwrap(), bob.id).is_err());
+
+        let req = RestoreRequestBody {
+            login: bob.username.clone(),
+            password: "wrong".to_string(),
+        };
+        let resp = user_restore(account_settings(), pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::UNAUTHORIZED);
+
+        let req = RestoreRequestBody {
+            login: bob.email.clone(),
+            password: "secret-42".to_string(),
+        };
+        let resp = user_restore(account_settings(), pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::OK);
+        assert!(UserRepo::find(&pool.get().unwrap(), bob.id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn restore_rejects_accounts_past_the_grace_period() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = UserRepo::create(
+            &conn,
+            NewUser {
+                username: Username().fake(),
+                password: "secret-42".to_string(),
+                email: FreeEmail().fake(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        UserRepo::schedule_deletion(&conn, bob.id).unwrap();
+        drop(conn);
+
+        let req = RestoreRequestBody {
+            login: bob.username,
+            password: "secret-42".to_string(),
+        };
+        let expired = AccountSettings {
+            deletion_grace: chrono::Duration::zero(),
+            ..account_settings()
+        };
+        let resp = user_restore(expired, pool.get().unwrap(), req)
+            .await
+            .unwrap();
+        assert_eq!(resp.into_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn delete_route_requires_bearer_token() {
+        let db = establish_connection();
+        let filter = routes(
+            db.clone(),
+            session_settings(),
+            account_settings(),
+            mailer(),
+        )
+        .recover(handle_rejection);
+        let resp = request()
+            .method("DELETE")
+            .path(&format!("/users/{}", Uuid::new_v4()))
+            .reply(&filter)
+            .await;
+
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn routes_respond_with_503_when_database_is_unreachable() {
+        let filter = routes(
+            unreachable_pool(),
+            session_settings(),
+            account_settings(),
+            mailer(),
+        )
+        .recover(handle_rejection);
+        let resp = request().method("GET").path("/users").reply(&filter).await;
+
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(resp.headers().contains_key("retry-after"));
     }
 }
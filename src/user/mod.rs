@@ -1,4 +1,7 @@
 pub mod handler;
-mod model;
-mod repository;
+pub mod model;
+pub mod password;
+pub mod purger;
+pub mod repository;
+pub mod validation;
 mod view;
@@ -1,10 +1,15 @@
-use diesel::{Insertable, Queryable};
+use chrono::NaiveDateTime;
+use diesel::{AsChangeset, Insertable, Queryable};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::pagination::Cursor;
 use crate::schema::users;
 
-use super::handler::RequestBody;
+use super::handler::{IndexQuery, RequestBody, UpdateRequestBody};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
 
 #[derive(Queryable, Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct User {
@@ -12,14 +17,129 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub password: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub is_admin: bool,
+    pub deactivated_at: Option<NaiveDateTime>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub email_verified_at: Option<NaiveDateTime>,
+    pub password_hashed_at: Option<NaiveDateTime>,
 }
 
-#[derive(Insertable, Serialize, PartialEq, Deserialize, Debug)]
+#[derive(Insertable, Serialize, PartialEq, Deserialize, Default, Debug)]
 #[table_name = "users"]
 pub struct NewUser {
     pub username: String,
     pub email: String,
     pub password: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
+}
+
+/// `None` leaves a column untouched; for the optional profile fields
+/// `Some(None)` clears it.
+#[derive(AsChangeset, PartialEq, Default, Debug)]
+#[table_name = "users"]
+pub struct UpdateUser {
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub display_name: Option<Option<String>>,
+    pub bio: Option<Option<String>>,
+    pub avatar_url: Option<Option<String>>,
+    pub location: Option<Option<String>>,
+    pub website: Option<Option<String>>,
+}
+
+/// How much of a profile the viewer may see, from least to most.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Visibility {
+    Public,
+    Follower,
+    Owner,
+    Admin,
+}
+
+impl Visibility {
+    /// `follows` tells whether the viewer follows `user`.
+    pub fn of(viewer: Option<&User>, user: &User, follows: bool) -> Self {
+        match viewer {
+            Some(viewer) if viewer.is_admin => Visibility::Admin,
+            Some(viewer) if viewer.id == user.id => Visibility::Owner,
+            Some(_) if follows => Visibility::Follower,
+            _ => Visibility::Public,
+        }
+    }
+
+    /// Location is shared with followers.
+    pub fn shows_location(self) -> bool {
+        self >= Visibility::Follower
+    }
+
+    /// Email and account metadata stay between the owner and admins.
+    pub fn shows_private(self) -> bool {
+        self >= Visibility::Owner
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Keyset page request: rows strictly after `cursor` in `order`.
+#[derive(PartialEq, Debug)]
+pub struct ListParams {
+    pub limit: i64,
+    pub cursor: Option<Cursor>,
+    pub order: SortOrder,
+    pub username_prefix: Option<String>,
+}
+
+impl NewUser {
+    /// Emails are stored lowercased; usernames keep their display casing and
+    /// rely on the `lower(username)` unique index instead.
+    pub fn normalized(self) -> Self {
+        NewUser {
+            email: normalize_email(&self.email),
+            ..self
+        }
+    }
+}
+
+impl UpdateUser {
+    pub fn is_empty(&self) -> bool {
+        self.username.is_none()
+            && self.email.is_none()
+            && self.password.is_none()
+            && self.display_name.is_none()
+            && self.bio.is_none()
+            && self.avatar_url.is_none()
+            && self.location.is_none()
+            && self.website.is_none()
+    }
+
+    pub fn normalized(self) -> Self {
+        UpdateUser {
+            email: self.email.as_deref().map(normalize_email),
+            ..self
+        }
+    }
+}
+
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
 }
 
 impl From<RequestBody> for NewUser {
@@ -28,10 +148,52 @@ impl From<RequestBody> for NewUser {
             username: req.username,
             password: req.password,
             email: req.email,
+            display_name: req.display_name,
+            bio: req.bio,
+            avatar_url: req.avatar_url,
+            location: req.location,
+            website: req.website,
+        }
+    }
+}
+
+impl From<IndexQuery> for ListParams {
+    fn from(query: IndexQuery) -> Self {
+        ListParams {
+            limit: query
+                .limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+            cursor: query.cursor,
+            order: query.order.unwrap_or_default(),
+            username_prefix: query
+                .username_prefix
+                .map(|prefix| prefix.trim().to_string())
+                .filter(|prefix| !prefix.is_empty()),
+        }
+    }
+}
+
+impl From<UpdateRequestBody> for UpdateUser {
+    fn from(req: UpdateRequestBody) -> Self {
+        UpdateUser {
+            username: req.username,
+            email: req.email,
+            password: req.password,
+            display_name: req.display_name.map(clearable),
+            bio: req.bio.map(clearable),
+            avatar_url: req.avatar_url.map(clearable),
+            location: req.location.map(clearable),
+            website: req.website.map(clearable),
         }
     }
 }
 
+/// An empty profile field in an update request clears the stored value.
+fn clearable(value: String) -> Option<String> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,16 +204,136 @@ mod tests {
             username: "Bob".to_string(),
             password: "password".to_string(),
             email: "bob@open.org".to_string(),
+            ..Default::default()
         };
 
         let expected = NewUser {
             username: "Bob".to_string(),
             password: "password".to_string(),
             email: "bob@open.org".to_string(),
+            ..Default::default()
         };
 
         let actual: NewUser = req_body.into();
 
         assert_eq!(expected, actual)
     }
+
+    #[test]
+    fn test_new_user_lowercases_email_but_keeps_username_casing() {
+        let req_body = RequestBody {
+            username: "Bob".to_string(),
+            password: "password".to_string(),
+            email: "Bob@Open.ORG".to_string(),
+            ..Default::default()
+        };
+
+        let actual = NewUser::from(req_body).normalized();
+
+        assert_eq!(actual.username, "Bob");
+        assert_eq!(actual.email, "bob@open.org");
+    }
+
+    #[test]
+    fn test_list_params_apply_defaults_and_clamp_limit() {
+        let actual: ListParams = IndexQuery::default().into();
+        let expected = ListParams {
+            limit: DEFAULT_PAGE_SIZE,
+            cursor: None,
+            order: SortOrder::Asc,
+            username_prefix: None,
+        };
+        assert_eq!(actual, expected);
+
+        let query = IndexQuery {
+            limit: Some(10_000),
+            order: Some(SortOrder::Desc),
+            username_prefix: Some("  ".to_string()),
+            ..Default::default()
+        };
+        let actual: ListParams = query.into();
+        assert_eq!(actual.limit, MAX_PAGE_SIZE);
+        assert_eq!(actual.order, SortOrder::Desc);
+        assert_eq!(actual.username_prefix, None);
+    }
+
+    #[test]
+    fn test_creates_update_user_from_update_request_body() {
+        let req_body = UpdateRequestBody {
+            username: Some("Bob".to_string()),
+            password: Some("new-password".to_string()),
+            current_password: Some("password".to_string()),
+            bio: Some(String::new()),
+            website: Some("https://open.org".to_string()),
+            ..Default::default()
+        };
+
+        let expected = UpdateUser {
+            username: Some("Bob".to_string()),
+            password: Some("new-password".to_string()),
+            bio: Some(None),
+            website: Some(Some("https://open.org".to_string())),
+            ..Default::default()
+        };
+
+        let actual: UpdateUser = req_body.into();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn test_visibility_depends_on_viewer() {
+        let bob = fake_user(false);
+        let alice = fake_user(false);
+        let admin = fake_user(true);
+
+        assert_eq!(Visibility::of(None, &bob, false), Visibility::Public);
+        assert_eq!(
+            Visibility::of(Some(&alice), &bob, false),
+            Visibility::Public
+        );
+        assert_eq!(
+            Visibility::of(Some(&alice), &bob, true),
+            Visibility::Follower
+        );
+        assert_eq!(Visibility::of(Some(&bob), &bob, false), Visibility::Owner);
+        assert_eq!(
+            Visibility::of(Some(&admin), &bob, false),
+            Visibility::Admin
+        );
+        assert!(Visibility::Follower.shows_location());
+        assert!(!Visibility::Follower.shows_private());
+    }
+
+    fn fake_user(is_admin: bool) -> User {
+        let now = chrono::Utc::now().naive_utc();
+        User {
+            id: Uuid::new_v4(),
+            username: "bob".to_string(),
+            email: "bob@open.org".to_string(),
+            password: "secret".to_string(),
+            display_name: None,
+            bio: None,
+            avatar_url: None,
+            location: None,
+            website: None,
+            created_at: now,
+            updated_at: now,
+            is_admin,
+            deactivated_at: None,
+            deleted_at: None,
+            email_verified_at: None,
+            password_hashed_at: None,
+        }
+    }
+
+    #[test]
+    fn test_update_user_is_empty_without_changes() {
+        assert!(UpdateUser::default().is_empty());
+        assert!(!UpdateUser {
+            email: Some("bob@open.org".to_string()),
+            ..Default::default()
+        }
+        .is_empty());
+    }
 }
@@ -0,0 +1,142 @@
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{
+    Error, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+
+#[derive(PartialEq, Debug)]
+pub enum Verification {
+    Invalid,
+    Valid,
+    NeedsRehash,
+}
+
+fn hasher() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::default())
+}
+
+pub fn hash(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = hasher().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Checks a password stored before hashing was introduced.
+pub fn verify_plaintext(password: &str, stored: &str) -> Verification {
+    if constant_time_eq(password.as_bytes(), stored.as_bytes()) {
+        Verification::NeedsRehash
+    } else {
+        Verification::Invalid
+    }
+}
+
+pub fn verify(password: &str, stored: &str) -> Verification {
+    let parsed = match PasswordHash::new(stored) {
+        Ok(parsed) => parsed,
+        Err(_) => return Verification::Invalid,
+    };
+
+    if hasher()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_err()
+    {
+        return Verification::Invalid;
+    }
+
+    if is_outdated(&parsed) {
+        Verification::NeedsRehash
+    } else {
+        Verification::Valid
+    }
+}
+
+/// Spends the same Argon2 work as `verify` against a throwaway hash, so a
+/// login naming no account takes as long to fail as a wrong password.
+pub fn verify_dummy(password: &str) {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    let stored = DUMMY.get_or_init(|| {
+        hash("not the password").expect("Couldn't hash dummy password")
+    });
+    verify(password, stored);
+}
+
+fn is_outdated(parsed: &PasswordHash) -> bool {
+    let current = Params::default();
+    let outdated_params = match Params::try_from(parsed) {
+        Ok(params) => {
+            params.m_cost() != current.m_cost()
+                || params.t_cost() != current.t_cost()
+                || params.p_cost() != current.p_cost()
+        }
+        Err(_) => true,
+    };
+
+    parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+        || outdated_params
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_produces_salted_argon2id_phc_string() {
+        let first = hash("password").unwrap();
+        let second = hash("password").unwrap();
+
+        assert!(first.starts_with("$argon2id$v=19$"));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn verify_accepts_correct_password() {
+        let stored = hash("password").unwrap();
+        assert_eq!(verify("password", &stored), Verification::Valid);
+    }
+
+    #[test]
+    fn verify_rejects_wrong_password() {
+        let stored = hash("password").unwrap();
+        assert_eq!(verify("passw0rd", &stored), Verification::Invalid);
+    }
+
+    #[test]
+    fn verify_plaintext_flags_match_for_rehash() {
+        assert_eq!(
+            verify_plaintext("password", "password"),
+            Verification::NeedsRehash
+        );
+        assert_eq!(
+            verify_plaintext("passw0rd", "password"),
+            Verification::Invalid
+        );
+    }
+
+    #[test]
+    fn verify_rejects_plaintext_that_looks_like_a_hash() {
+        assert_eq!(verify("$argon2id", "$argon2id"), Verification::Invalid);
+    }
+
+    #[test]
+    fn verify_flags_outdated_params_for_rehash() {
+        let params = Params::new(8, 1, 1, None).unwrap();
+        let salt = SaltString::generate(&mut OsRng);
+        let stored = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(b"password", &salt)
+            .unwrap()
+            .to_string();
+
+        assert_eq!(verify("password", &stored), Verification::NeedsRehash);
+    }
+}
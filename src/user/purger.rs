@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use tokio::{task, time};
+
+use crate::config::AccountSettings;
+use crate::ConnectionPool;
+
+use super::repository::UserRepo;
+
+/// Hard-deletes every account whose deletion grace period has run out.
+pub fn purge(
+    pool: &ConnectionPool,
+    grace: chrono::Duration,
+) -> Result<usize, String> {
+    let conn = pool.get().map_err(|err| err.to_string())?;
+    UserRepo::purge_expired(&conn, grace).map_err(|err| err.to_string())
+}
+
+/// Purges once per `interval` for as long as the server runs.
+pub async fn run(
+    pool: ConnectionPool,
+    settings: AccountSettings,
+    interval: Duration,
+) {
+    let mut ticks = time::interval(interval);
+    loop {
+        ticks.tick().await;
+        let pool = pool.clone();
+        let grace = settings.deletion_grace;
+        match task::spawn_blocking(move || purge(&pool, grace)).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(count)) => info!("Purged {} deleted accounts", count),
+            Ok(Err(err)) => error!("Couldn't purge deleted accounts: {}", err),
+            Err(err) => error!("Account purge task failed: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+    use fake::faker::internet::en::{FreeEmail, Password, Username};
+    use fake::Fake;
+
+    use crate::schema::users;
+    use crate::test_helpers::{account_settings, establish_connection};
+    use crate::user::model::{NewUser, User};
+
+    use super::*;
+
+    #[test]
+    fn purge_removes_accounts_deleted_before_the_grace_period() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let user = NewUser {
+            username: Username().fake(),
+            password: Password(8..12).fake(),
+            email: FreeEmail().fake(),
+            ..Default::default()
+        };
+        let bob: User = diesel::insert_into(users::table)
+            .values(&user)
+            .get_result(&conn)
+            .unwrap();
+        diesel::update(users::table.find(bob.id))
+            .set(
+                users::deleted_at
+                    .eq(Utc::now().naive_utc() - chrono::Duration::days(60)),
+            )
+            .execute(&conn)
+            .unwrap();
+        drop(conn);
+
+        let grace = account_settings().deletion_grace;
+        assert_eq!(purge(&pool, grace), Ok(1));
+        assert_eq!(purge(&pool, grace), Ok(0));
+    }
+}
@@ -1,47 +1,299 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::dsl::{now, And, Filter, IsNull, Select};
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use diesel::result::Error;
 use diesel::QueryResult;
 use uuid::Uuid;
 
+use crate::pagination::{into_page, Cursor};
 use crate::schema::users;
 use crate::schema::users::dsl::*;
-use crate::user::model::NewUser;
+use crate::user::model::{ListParams, NewUser, SortOrder, UpdateUser};
 
 use super::model::User;
+use super::password::{self, Verification};
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
 
 pub struct UserRepo;
 
 impl UserRepo {
-    pub fn read_all(conn: &PgConnection) -> Vec<User> {
-        users.load::<User>(conn).unwrap()
+    /// Fetches one page plus a look-ahead row, ordered by `(created_at, id)`;
+    /// the second value is the cursor for the following page, if any.
+    pub fn list(
+        conn: &PgConnection,
+        params: &ListParams,
+    ) -> QueryResult<(Vec<User>, Option<Cursor>)> {
+        let mut query = active_users();
+
+        if let Some(prefix) = &params.username_prefix {
+            let pattern = format!("{}%", escape_like(&prefix.to_lowercase()));
+            query = query.filter(lower(username).like(pattern));
+        }
+
+        query = match (params.order, params.cursor) {
+            (SortOrder::Asc, Some(cursor)) => query.filter(
+                created_at
+                    .gt(cursor.at)
+                    .or(created_at.eq(cursor.at).and(id.gt(cursor.id))),
+            ),
+            (SortOrder::Desc, Some(cursor)) => query.filter(
+                created_at
+                    .lt(cursor.at)
+                    .or(created_at.eq(cursor.at).and(id.lt(cursor.id))),
+            ),
+            (_, None) => query,
+        };
+        query = match params.order {
+            SortOrder::Asc => query.order((created_at.asc(), id.asc())),
+            SortOrder::Desc => query.order((created_at.desc(), id.desc())),
+        };
+
+        let page = query.limit(params.limit + 1).load::<User>(conn)?;
+        Ok(into_page(page, params.limit, |user| {
+            Cursor::new(user.created_at, user.id)
+        }))
     }
 
     pub fn create(conn: &PgConnection, new_user: NewUser) -> QueryResult<User> {
+        let new_user = NewUser {
+            password: hash_password(&new_user.password)?,
+            ..new_user.normalized()
+        };
+
         diesel::insert_into(users::table)
-            .values(new_user)
+            .values((new_user, password_hashed_at.eq(now.nullable())))
             .get_result(conn)
     }
 
     pub fn find(conn: &PgConnection, user_id: Uuid) -> QueryResult<User> {
-        users.find(user_id).first(conn)
+        active_users().filter(id.eq(user_id)).first(conn)
+    }
+
+    /// Usernames are unique case-insensitively, so lookups are too.
+    pub fn find_by_username(
+        conn: &PgConnection,
+        name: &str,
+    ) -> QueryResult<User> {
+        active_users()
+            .filter(lower(username).eq(name.trim().to_lowercase()))
+            .first(conn)
     }
 
-    pub fn delete(conn: &PgConnection, user_id: Uuid) -> QueryResult<usize> {
-        diesel::delete(users.filter(id.eq(user_id))).execute(conn)
+    pub fn find_by_login(
+        conn: &PgConnection,
+        login: &str,
+    ) -> QueryResult<User> {
+        let login = login.trim().to_lowercase();
+        active_users()
+            .filter(lower(username).eq(&login).or(lower(email).eq(&login)))
+            .first(conn)
     }
+
+    /// An account that was deactivated, or deleted less than `grace` ago,
+    /// and so can still be restored.
+    pub fn find_restorable(
+        conn: &PgConnection,
+        login: &str,
+        grace: Duration,
+    ) -> QueryResult<User> {
+        let login = login.trim().to_lowercase();
+        users
+            .filter(lower(username).eq(&login).or(lower(email).eq(&login)))
+            .filter(deleted_at.is_not_null().or(deactivated_at.is_not_null()))
+            .filter(deleted_at.is_null().or(deleted_at.gt(purge_cutoff(grace))))
+            .first(conn)
+    }
+
+    pub fn update(
+        conn: &PgConnection,
+        user_id: Uuid,
+        changes: UpdateUser,
+    ) -> QueryResult<User> {
+        if changes.is_empty() {
+            return UserRepo::find(conn, user_id);
+        }
+
+        let changes = UpdateUser {
+            password: changes
+                .password
+                .as_deref()
+                .map(hash_password)
+                .transpose()?,
+            ..changes.normalized()
+        };
+
+        conn.transaction(|| {
+            // A new address has to be verified again.
+            if let Some(new_email) = &changes.email {
+                diesel::update(users.find(user_id).filter(email.ne(new_email)))
+                    .set(email_verified_at.eq(None::<NaiveDateTime>))
+                    .execute(conn)?;
+            }
+            if changes.password.is_some() {
+                diesel::update(users.find(user_id))
+                    .set(password_hashed_at.eq(now.nullable()))
+                    .execute(conn)?;
+            }
+            diesel::update(users.find(user_id))
+                .set(changes)
+                .get_result(conn)
+        })
+    }
+
+    /// Hides the account straight away; `purge_expired` removes the row once
+    /// the grace period is over.
+    pub fn schedule_deletion(
+        conn: &PgConnection,
+        user_id: Uuid,
+    ) -> QueryResult<User> {
+        diesel::update(
+            users.filter(id.eq(user_id)).filter(deleted_at.is_null()),
+        )
+        .set(deleted_at.eq(now.nullable()))
+        .get_result(conn)
+    }
+
+    /// Hides the account until its owner restores it.
+    pub fn deactivate(conn: &PgConnection, user_id: Uuid) -> QueryResult<User> {
+        diesel::update(
+            users
+                .filter(id.eq(user_id))
+                .filter(deactivated_at.is_null()),
+        )
+        .set(deactivated_at.eq(now.nullable()))
+        .get_result(conn)
+    }
+
+    pub fn restore(conn: &PgConnection, user_id: Uuid) -> QueryResult<User> {
+        diesel::update(users.find(user_id))
+            .set((
+                deleted_at.eq(None::<NaiveDateTime>),
+                deactivated_at.eq(None::<NaiveDateTime>),
+            ))
+            .get_result(conn)
+    }
+
+    /// Hard-deletes accounts deleted more than `grace` ago, returning how
+    /// many were removed.
+    pub fn purge_expired(
+        conn: &PgConnection,
+        grace: Duration,
+    ) -> QueryResult<usize> {
+        diesel::delete(users.filter(deleted_at.lt(purge_cutoff(grace))))
+            .execute(conn)
+    }
+
+    /// Checks `candidate` against the stored hash, transparently upgrading
+    /// plaintext or outdated hashes when the password matches.
+    pub fn verify_password(
+        conn: &PgConnection,
+        user: &User,
+        candidate: &str,
+    ) -> QueryResult<bool> {
+        let verification = match user.password_hashed_at {
+            Some(_) => password::verify(candidate, &user.password),
+            None => password::verify_plaintext(candidate, &user.password),
+        };
+        match verification {
+            Verification::Invalid => Ok(false),
+            Verification::Valid => Ok(true),
+            Verification::NeedsRehash => {
+                diesel::update(users.find(user.id))
+                    .set((
+                        password.eq(hash_password(candidate)?),
+                        password_hashed_at.eq(now.nullable()),
+                    ))
+                    .execute(conn)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Hashes up to `batch_size` passwords still stored as plaintext,
+    /// returning how many rows were migrated. No locks are held while
+    /// hashing: a row is only updated if it still holds the plaintext that
+    /// was hashed, so a concurrent password change wins.
+    pub fn hash_legacy_passwords(
+        conn: &PgConnection,
+        batch_size: i64,
+    ) -> QueryResult<usize> {
+        let legacy = users
+            .select((id, password))
+            .filter(password_hashed_at.is_null())
+            .order(id)
+            .limit(batch_size)
+            .load::<(Uuid, String)>(conn)?;
+
+        let mut migrated = 0;
+        for (user_id, plaintext) in &legacy {
+            migrated += diesel::update(
+                users
+                    .find(user_id)
+                    .filter(password_hashed_at.is_null())
+                    .filter(password.eq(plaintext)),
+            )
+            .set((
+                password.eq(hash_password(plaintext)?),
+                password_hashed_at.eq(now.nullable()),
+            ))
+            .execute(conn)?;
+        }
+        Ok(migrated)
+    }
+}
+
+pub type Active = And<IsNull<deleted_at>, IsNull<deactivated_at>>;
+
+/// Matches users who haven't deactivated or deleted their account. Other
+/// modules filter on it whenever they join `users`, so hidden accounts
+/// disappear everywhere.
+pub fn active() -> Active {
+    deleted_at.is_null().and(deactivated_at.is_null())
+}
+
+/// Ids of active users, for tables that can't join `users` directly.
+pub fn active_ids() -> Select<Filter<users::table, Active>, id> {
+    users.filter(active()).select(id)
+}
+
+fn active_users<'a>() -> users::BoxedQuery<'a, Pg> {
+    users.filter(active()).into_boxed()
+}
+
+fn purge_cutoff(grace: Duration) -> NaiveDateTime {
+    (Utc::now() - grace).naive_utc()
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn hash_password(plaintext: &str) -> QueryResult<String> {
+    password::hash(plaintext)
+        .map_err(|err| Error::SerializationError(err.into()))
 }
 
 #[cfg(test)]
 mod tests {
-    use diesel::{PgConnection, RunQueryDsl};
+    use chrono::{Duration, NaiveDateTime, Utc};
+    use diesel::{ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
     use fake::faker::internet::en::FreeEmail;
     use fake::faker::internet::en::Password;
     use fake::faker::name::en::Name;
     use fake::Fake;
     use uuid::Uuid;
 
+    use crate::pagination::Cursor;
     use crate::schema::users;
     use crate::test_helpers::establish_connection;
-    use crate::user::model::{NewUser, User};
+    use crate::user::model::{
+        ListParams, NewUser, SortOrder, UpdateUser, User,
+    };
     use crate::user::repository::UserRepo;
 
     fn create_fake_users(conn: &PgConnection) -> User {
@@ -49,26 +301,138 @@ mod tests {
             username: Name().fake(),
             password: Password(5..10).fake(),
             email: FreeEmail().fake(),
+            ..Default::default()
         };
-        let user = diesel::insert_into(users::table)
+        diesel::insert_into(users::table)
             .values(&user)
             .get_result(conn)
-            .expect("Failed to create fake user");
-        user
+            .expect("Failed to create fake user")
     }
 
     #[test]
-    fn read_all_returns_all_users() {
+    fn list_returns_all_users_within_limit() {
         let conn = establish_connection().get().unwrap();
         let bob = create_fake_users(&conn);
         let alice = create_fake_users(&conn);
 
-        let actual = UserRepo::read_all(&conn);
+        let (actual, next_cursor) =
+            UserRepo::list(&conn, &page_params(10, SortOrder::Asc)).unwrap();
+        assert_eq!(next_cursor, None);
         assert!(actual.contains(&bob));
         assert!(actual.contains(&alice));
         assert_eq!(actual.len(), 2);
     }
 
+    fn page_params(limit: i64, order: SortOrder) -> ListParams {
+        ListParams {
+            limit,
+            cursor: None,
+            order,
+            username_prefix: None,
+        }
+    }
+
+    /// Moves `user` to `at` so tests decide the listing order.
+    fn created_at_time(
+        conn: &PgConnection,
+        user: User,
+        at: NaiveDateTime,
+    ) -> User {
+        diesel::update(users::table.find(user.id))
+            .set(users::created_at.eq(at))
+            .get_result(conn)
+            .expect("Failed to backdate user")
+    }
+
+    /// Users created out of order, two of them in the same microsecond.
+    fn create_users_at_mixed_times(conn: &PgConnection) -> Vec<User> {
+        let base = Utc::now().naive_utc();
+        [3, 1, 1, 0, 2]
+            .iter()
+            .map(|secs| {
+                let user = create_fake_users(conn);
+                created_at_time(conn, user, base - Duration::seconds(*secs))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn list_pages_through_users_by_creation_time() {
+        let conn = establish_connection().get().unwrap();
+        let mut expected = create_users_at_mixed_times(&conn);
+        expected.sort_by_key(|user| (user.created_at, user.id));
+
+        let mut params = page_params(2, SortOrder::Asc);
+        let mut actual = Vec::new();
+        loop {
+            let (page, next_cursor) = UserRepo::list(&conn, &params).unwrap();
+            assert!(page.len() <= 2);
+            actual.extend(page);
+            match next_cursor {
+                Some(cursor) => params.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn list_supports_descending_order() {
+        let conn = establish_connection().get().unwrap();
+        let mut expected = create_users_at_mixed_times(&conn);
+        expected
+            .sort_by_key(|user| std::cmp::Reverse((user.created_at, user.id)));
+
+        let (page, next_cursor) =
+            UserRepo::list(&conn, &page_params(3, SortOrder::Desc)).unwrap();
+        assert_eq!(page, expected[..3].to_vec());
+        assert_eq!(
+            next_cursor,
+            Some(Cursor::new(expected[2].created_at, expected[2].id))
+        );
+
+        let params = ListParams {
+            cursor: next_cursor,
+            ..page_params(3, SortOrder::Desc)
+        };
+        let (page, next_cursor) = UserRepo::list(&conn, &params).unwrap();
+        assert_eq!(page, expected[3..].to_vec());
+        assert_eq!(next_cursor, None);
+    }
+
+    #[test]
+    fn list_filters_by_case_insensitive_username_prefix() {
+        let conn = establish_connection().get().unwrap();
+        let bob = UserRepo::create(
+            &conn,
+            NewUser {
+                username: "Bob_smith".to_string(),
+                password: "password".to_string(),
+                email: "bob@open.org".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        UserRepo::create(
+            &conn,
+            NewUser {
+                username: "bobXsmith".to_string(),
+                password: "password".to_string(),
+                email: "bobx@open.org".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let params = ListParams {
+            username_prefix: Some("bob_".to_string()),
+            ..page_params(10, SortOrder::Asc)
+        };
+        let (page, _) = UserRepo::list(&conn, &params).unwrap();
+        assert_eq!(page, vec![bob]);
+    }
+
     #[test]
     fn creates_users_for_valid_data() {
         let conn = establish_connection().get().unwrap();
@@ -76,12 +440,99 @@ mod tests {
             username: "bob".to_string(),
             password: "password".to_string(),
             email: "bob@open.org".to_string(),
+            ..Default::default()
         };
 
         let result = UserRepo::create(&conn, bob);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn create_stores_hashed_password() {
+        let conn = establish_connection().get().unwrap();
+        let bob = NewUser {
+            username: "bob".to_string(),
+            password: "password".to_string(),
+            email: "bob@open.org".to_string(),
+            ..Default::default()
+        };
+
+        let bob = UserRepo::create(&conn, bob).unwrap();
+        assert!(bob.password_hashed_at.is_some());
+        assert_eq!(
+            UserRepo::verify_password(&conn, &bob, "password"),
+            Ok(true)
+        );
+        assert_eq!(UserRepo::verify_password(&conn, &bob, "wrong"), Ok(false));
+    }
+
+    #[test]
+    fn verify_password_rehashes_legacy_plaintext() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let plaintext = bob.password.clone();
+
+        let result = UserRepo::verify_password(&conn, &bob, &plaintext);
+        assert_eq!(result, Ok(true));
+
+        let bob = UserRepo::find(&conn, bob.id).unwrap();
+        assert!(bob.password_hashed_at.is_some());
+        assert_eq!(
+            UserRepo::verify_password(&conn, &bob, &plaintext),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn verify_password_migrates_plaintext_that_looks_like_a_hash() {
+        let conn = establish_connection().get().unwrap();
+        let bob = NewUser {
+            username: "bob".to_string(),
+            password: "$argon2id$v=19$hunter2".to_string(),
+            email: "bob@open.org".to_string(),
+            ..Default::default()
+        };
+        let bob: User = diesel::insert_into(users::table)
+            .values(&bob)
+            .get_result(&conn)
+            .unwrap();
+
+        let result = UserRepo::verify_password(&conn, &bob, &bob.password);
+        assert_eq!(result, Ok(true));
+
+        let migrated = UserRepo::find(&conn, bob.id).unwrap();
+        assert!(migrated.password_hashed_at.is_some());
+        assert_ne!(migrated.password, bob.password);
+        assert_eq!(
+            UserRepo::verify_password(&conn, &migrated, &bob.password),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn hash_legacy_passwords_hashes_only_plaintext_rows() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = NewUser {
+            username: Name().fake(),
+            password: Password(5..10).fake(),
+            email: FreeEmail().fake(),
+            ..Default::default()
+        };
+        let alice = UserRepo::create(&conn, alice).unwrap();
+
+        assert_eq!(UserRepo::hash_legacy_passwords(&conn, 10), Ok(1));
+        assert_eq!(UserRepo::hash_legacy_passwords(&conn, 10), Ok(0));
+
+        let migrated = UserRepo::find(&conn, bob.id).unwrap();
+        assert!(migrated.password_hashed_at.is_some());
+        assert_eq!(
+            UserRepo::verify_password(&conn, &migrated, &bob.password),
+            Ok(true)
+        );
+        assert_eq!(UserRepo::find(&conn, alice.id), Ok(alice));
+    }
+
     #[test]
     fn create_user_returns_error_fo_invalid_data() {
         let conn = establish_connection().get().unwrap();
@@ -90,6 +541,7 @@ mod tests {
             username: bob.username,
             password: bob.password,
             email: bob.email,
+            ..Default::default()
         };
 
         let result = UserRepo::create(&conn, bob);
@@ -115,20 +567,207 @@ mod tests {
     }
 
     #[test]
-    fn deletes_user_for_valid_id() {
+    fn find_by_login_matches_username_or_email() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+
+        assert_eq!(
+            UserRepo::find_by_login(&conn, &bob.username),
+            Ok(bob.clone())
+        );
+        assert_eq!(UserRepo::find_by_login(&conn, &bob.email), Ok(bob));
+        assert!(UserRepo::find_by_login(&conn, "nobody").is_err());
+    }
+
+    #[test]
+    fn find_by_login_ignores_case() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+
+        let actual =
+            UserRepo::find_by_login(&conn, &bob.username.to_uppercase());
+        assert_eq!(actual, Ok(bob.clone()));
+
+        let actual = UserRepo::find_by_login(&conn, &bob.email.to_uppercase());
+        assert_eq!(actual, Ok(bob));
+    }
+
+    #[test]
+    fn create_lowercases_email() {
+        let conn = establish_connection().get().unwrap();
+        let bob = NewUser {
+            username: "Bob".to_string(),
+            password: "password".to_string(),
+            email: "Bob@Open.ORG".to_string(),
+            ..Default::default()
+        };
+
+        let bob = UserRepo::create(&conn, bob).unwrap();
+        assert_eq!(bob.username, "Bob");
+        assert_eq!(bob.email, "bob@open.org");
+    }
+
+    #[test]
+    fn create_rejects_usernames_and_emails_differing_only_by_case() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+
+        let same_username = NewUser {
+            username: bob.username.to_uppercase(),
+            password: "password".to_string(),
+            email: FreeEmail().fake(),
+            ..Default::default()
+        };
+        assert!(UserRepo::create(&conn, same_username).is_err());
+
+        let same_email = NewUser {
+            username: Name().fake(),
+            password: "password".to_string(),
+            email: bob.email.to_uppercase(),
+            ..Default::default()
+        };
+        assert!(UserRepo::create(&conn, same_email).is_err());
+    }
+
+    #[test]
+    fn update_changes_only_given_fields() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let changes = UpdateUser {
+            email: Some("bob@open.org".to_string()),
+            ..Default::default()
+        };
+
+        let actual = UserRepo::update(&conn, bob.id, changes).unwrap();
+        assert_eq!(actual.email, "bob@open.org");
+        assert_eq!(actual.username, bob.username);
+        assert_eq!(actual.password, bob.password);
+    }
+
+    #[test]
+    fn update_clears_verification_when_email_changes() {
         let conn = establish_connection().get().unwrap();
         let bob = create_fake_users(&conn);
+        diesel::update(users::table.find(bob.id))
+            .set(users::email_verified_at.eq(Utc::now().naive_utc()))
+            .execute(&conn)
+            .unwrap();
 
-        let result = UserRepo::delete(&conn, bob.id);
-        assert_eq!(result, Ok(1));
+        let same_email = UpdateUser {
+            email: Some(bob.email.clone()),
+            ..Default::default()
+        };
+        let actual = UserRepo::update(&conn, bob.id, same_email).unwrap();
+        assert!(actual.email_verified_at.is_some());
+
+        let new_email = UpdateUser {
+            email: Some("bob@open.org".to_string()),
+            ..Default::default()
+        };
+        let actual = UserRepo::update(&conn, bob.id, new_email).unwrap();
+        assert_eq!(actual.email_verified_at, None);
     }
 
     #[test]
-    fn delete_returns_error_for_non_existent_user() {
+    fn update_hashes_new_password() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let changes = UpdateUser {
+            password: Some("new-password".to_string()),
+            ..Default::default()
+        };
+
+        let actual = UserRepo::update(&conn, bob.id, changes).unwrap();
+        assert!(actual.password_hashed_at.is_some());
+        assert_eq!(
+            UserRepo::verify_password(&conn, &actual, "new-password"),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn update_without_changes_returns_user() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+
+        let actual = UserRepo::update(&conn, bob.id, UpdateUser::default());
+        assert_eq!(actual, Ok(bob));
+    }
+
+    #[test]
+    fn update_returns_error_for_duplicate_username() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = create_fake_users(&conn);
+        let changes = UpdateUser {
+            username: Some(alice.username),
+            ..Default::default()
+        };
+
+        let result = UserRepo::update(&conn, bob.id, changes);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn schedule_deletion_hides_user_from_lookups() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+
+        let deleted = UserRepo::schedule_deletion(&conn, bob.id).unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert!(UserRepo::find(&conn, bob.id).is_err());
+        assert!(UserRepo::find_by_login(&conn, &bob.email).is_err());
+        let (page, _) =
+            UserRepo::list(&conn, &page_params(10, SortOrder::Asc)).unwrap();
+        assert!(page.is_empty());
+    }
+
+    #[test]
+    fn schedule_deletion_returns_error_for_non_existent_user() {
         let conn = establish_connection().get().unwrap();
         let id = Uuid::new_v4();
 
-        let result = UserRepo::delete(&conn, id);
-        assert_eq!(result, Ok(0));
+        let result = UserRepo::schedule_deletion(&conn, id);
+        assert_eq!(result, Err(diesel::result::Error::NotFound));
+    }
+
+    #[test]
+    fn restore_brings_back_deactivated_user() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        UserRepo::deactivate(&conn, bob.id).unwrap();
+
+        let restorable =
+            UserRepo::find_restorable(&conn, &bob.username, Duration::days(30));
+        assert_eq!(restorable.map(|user| user.id), Ok(bob.id));
+
+        UserRepo::restore(&conn, bob.id).unwrap();
+        assert_eq!(UserRepo::find(&conn, bob.id), Ok(bob));
+    }
+
+    #[test]
+    fn purge_expired_removes_only_accounts_past_grace() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_fake_users(&conn);
+        let alice = create_fake_users(&conn);
+        create_fake_users(&conn);
+        UserRepo::schedule_deletion(&conn, alice.id).unwrap();
+        diesel::update(users::table.find(bob.id))
+            .set(
+                users::deleted_at
+                    .eq(Utc::now().naive_utc() - Duration::days(31)),
+            )
+            .execute(&conn)
+            .unwrap();
+
+        assert_eq!(UserRepo::purge_expired(&conn, Duration::days(30)), Ok(1));
+        let remaining: i64 = users::table.count().get_result(&conn).unwrap();
+        assert_eq!(remaining, 2);
+        assert!(UserRepo::find_restorable(
+            &conn,
+            &alice.username,
+            Duration::days(30)
+        )
+        .is_ok());
     }
 }
@@ -0,0 +1,378 @@
+use crate::error::{ApiError, FieldError};
+
+use super::handler::{RequestBody, UpdateRequestBody};
+
+const USERNAME_LENGTH: (usize, usize) = (3, 30);
+const PASSWORD_LENGTH: (usize, usize) = (8, 128);
+const EMAIL_MAX_LENGTH: usize = 254;
+const EMAIL_LOCAL_MAX_LENGTH: usize = 64;
+const DISPLAY_NAME_MAX_LENGTH: usize = 50;
+const BIO_MAX_LENGTH: usize = 500;
+const LOCATION_MAX_LENGTH: usize = 100;
+const URL_MAX_LENGTH: usize = 2048;
+
+/// Trims the registration payload and checks every field, collecting all
+/// failures so clients can surface them at once.
+pub fn validate_new_user(req: RequestBody) -> Result<RequestBody, ApiError> {
+    let req = RequestBody {
+        username: req.username.trim().to_string(),
+        email: req.email.trim().to_string(),
+        password: req.password,
+        display_name: trim(req.display_name).filter(|v| !v.is_empty()),
+        bio: trim(req.bio).filter(|v| !v.is_empty()),
+        avatar_url: trim(req.avatar_url).filter(|v| !v.is_empty()),
+        location: trim(req.location).filter(|v| !v.is_empty()),
+        website: trim(req.website).filter(|v| !v.is_empty()),
+    };
+
+    let errors: Vec<FieldError> = vec![
+        validate_username(&req.username),
+        validate_email(&req.email),
+        validate_password(&req.password, &[&req.username, &req.email]),
+        validate_text(
+            "display_name",
+            &req.display_name,
+            DISPLAY_NAME_MAX_LENGTH,
+        ),
+        validate_text("bio", &req.bio, BIO_MAX_LENGTH),
+        validate_url("avatar_url", &req.avatar_url),
+        validate_text("location", &req.location, LOCATION_MAX_LENGTH),
+        validate_url("website", &req.website),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if errors.is_empty() {
+        Ok(req)
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
+
+pub fn validate_update(
+    req: UpdateRequestBody,
+) -> Result<UpdateRequestBody, ApiError> {
+    let req = UpdateRequestBody {
+        username: req.username.map(|username| username.trim().to_string()),
+        email: req.email.map(|email| email.trim().to_string()),
+        display_name: trim(req.display_name),
+        bio: trim(req.bio),
+        avatar_url: trim(req.avatar_url),
+        location: trim(req.location),
+        website: trim(req.website),
+        ..req
+    };
+
+    let mut errors = Vec::new();
+    if let Some(username) = &req.username {
+        errors.extend(validate_username(username));
+    }
+    if let Some(email) = &req.email {
+        errors.extend(validate_email(email));
+    }
+    if let Some(password) = &req.password {
+        let identifiers: Vec<&str> = req
+            .username
+            .iter()
+            .chain(&req.email)
+            .map(String::as_str)
+            .collect();
+        errors.extend(validate_password(password, &identifiers));
+    }
+    errors.extend(validate_text(
+        "display_name",
+        &req.display_name,
+        DISPLAY_NAME_MAX_LENGTH,
+    ));
+    errors.extend(validate_text("bio", &req.bio, BIO_MAX_LENGTH));
+    errors.extend(validate_url("avatar_url", &req.avatar_url));
+    errors.extend(validate_text(
+        "location",
+        &req.location,
+        LOCATION_MAX_LENGTH,
+    ));
+    errors.extend(validate_url("website", &req.website));
+
+    if errors.is_empty() {
+        Ok(req)
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
+
+fn validate_username(username: &str) -> Option<FieldError> {
+    let (min, max) = USERNAME_LENGTH;
+    let length = username.chars().count();
+
+    if length < min || length > max {
+        return Some(FieldError::new(
+            "username",
+            format!("must be between {} and {} characters", min, max),
+        ));
+    }
+
+    let valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-');
+    if !valid_chars {
+        return Some(FieldError::new(
+            "username",
+            "may only contain letters, digits, '_', '.' and '-'",
+        ));
+    }
+
+    if !username.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Some(FieldError::new(
+            "username",
+            "must start with a letter or digit",
+        ));
+    }
+
+    None
+}
+
+fn validate_email(email: &str) -> Option<FieldError> {
+    if is_valid_email(email) {
+        None
+    } else {
+        Some(FieldError::new("email", "must be a valid email address"))
+    }
+}
+
+fn is_valid_email(email: &str) -> bool {
+    if email.len() > EMAIL_MAX_LENGTH {
+        return false;
+    }
+
+    let (local, domain) = match email.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let valid_local = !local.is_empty()
+        && local.len() <= EMAIL_LOCAL_MAX_LENGTH
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local.chars().all(|c| {
+            c.is_ascii_alphanumeric() || "!#$%&'*+/=?^_`{|}~.-".contains(c)
+        });
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_domain = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels.last().is_some_and(|tld| {
+            tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic())
+        });
+
+    valid_local && valid_domain
+}
+
+pub fn validate_password(
+    password: &str,
+    identifiers: &[&str],
+) -> Option<FieldError> {
+    let (min, max) = PASSWORD_LENGTH;
+    let length = password.chars().count();
+
+    if length < min || length > max {
+        return Some(FieldError::new(
+            "password",
+            format!("must be between {} and {} characters", min, max),
+        ));
+    }
+
+    let has_letter = password.chars().any(char::is_alphabetic);
+    let has_other = password.chars().any(|c| !c.is_alphabetic());
+    if !has_letter || !has_other {
+        return Some(FieldError::new(
+            "password",
+            "must contain a letter and a digit or symbol",
+        ));
+    }
+
+    let lowered = password.to_lowercase();
+    if identifiers
+        .iter()
+        .any(|identifier| lowered == identifier.to_lowercase())
+    {
+        return Some(FieldError::new(
+            "password",
+            "must not match the username or email",
+        ));
+    }
+
+    None
+}
+
+fn trim(value: Option<String>) -> Option<String> {
+    value.map(|value| value.trim().to_string())
+}
+
+/// Free-text profile fields only have a length limit; empty values clear
+/// the field and always pass.
+fn validate_text(
+    field: &str,
+    value: &Option<String>,
+    max: usize,
+) -> Option<FieldError> {
+    match value {
+        Some(value) if value.chars().count() > max => Some(FieldError::new(
+            field,
+            format!("must be at most {} characters", max),
+        )),
+        _ => None,
+    }
+}
+
+fn validate_url(field: &str, value: &Option<String>) -> Option<FieldError> {
+    match value {
+        Some(value) if !value.is_empty() && !is_valid_url(value) => {
+            Some(FieldError::new(field, "must be a valid http or https URL"))
+        }
+        _ => None,
+    }
+}
+
+fn is_valid_url(url: &str) -> bool {
+    let rest = match url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let host = rest.split(['/', '?', '#']).next();
+
+    url.len() <= URL_MAX_LENGTH
+        && host.is_some_and(|host| !host.is_empty())
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(username: &str, email: &str, password: &str) -> RequestBody {
+        RequestBody {
+            username: username.to_string(),
+            email: email.to_string(),
+            password: password.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn fields(err: ApiError) -> Vec<String> {
+        match err {
+            ApiError::Validation(errors) => {
+                errors.into_iter().map(|err| err.field).collect()
+            }
+            err => panic!("Expected validation error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn validate_new_user_trims_username_and_email() {
+        let req = request("  bob ", " bob@open.org\n", "secret-42");
+
+        let actual = validate_new_user(req).unwrap();
+        assert_eq!(actual.username, "bob");
+        assert_eq!(actual.email, "bob@open.org");
+        assert_eq!(actual.password, "secret-42");
+    }
+
+    #[test]
+    fn validate_new_user_collects_errors_for_every_field() {
+        let req = request("b", "not-an-email", "short");
+
+        let err = validate_new_user(req).unwrap_err();
+        assert_eq!(fields(err), vec!["username", "email", "password"]);
+    }
+
+    #[test]
+    fn username_must_use_allowed_charset() {
+        assert!(validate_username("bob.smith_99").is_none());
+        assert!(validate_username("bob smith").is_some());
+        assert!(validate_username("_bob").is_some());
+        assert!(validate_username(&"b".repeat(31)).is_some());
+    }
+
+    #[test]
+    fn email_must_be_well_formed() {
+        assert!(is_valid_email("bob@open.org"));
+        assert!(is_valid_email("bob.smith+tag@mail.open.org"));
+        assert!(!is_valid_email("bob"));
+        assert!(!is_valid_email("bob@open"));
+        assert!(!is_valid_email("@open.org"));
+        assert!(!is_valid_email("bob..smith@open.org"));
+        assert!(!is_valid_email("bob@-open.org"));
+        assert!(!is_valid_email("bob@open.o"));
+    }
+
+    #[test]
+    fn password_must_meet_strength_policy() {
+        assert!(validate_password("secret-42", &[]).is_none());
+        assert!(validate_password("short1", &[]).is_some());
+        assert!(validate_password("lettersonly", &[]).is_some());
+        assert!(validate_password("12345678", &[]).is_some());
+        assert!(validate_password("Bob.Smith1", &["bob.smith1"]).is_some());
+    }
+
+    #[test]
+    fn validate_update_checks_only_present_fields() {
+        let req = UpdateRequestBody {
+            email: Some(" bob@open.org ".to_string()),
+            ..Default::default()
+        };
+        let actual = validate_update(req).unwrap();
+        assert_eq!(actual.email, Some("bob@open.org".to_string()));
+
+        let req = UpdateRequestBody {
+            password: Some("short".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(fields(validate_update(req).unwrap_err()), vec!["password"]);
+    }
+
+    #[test]
+    fn validate_new_user_drops_blank_profile_fields() {
+        let req = RequestBody {
+            display_name: Some("  Bob Smith ".to_string()),
+            bio: Some("   ".to_string()),
+            ..request("bob", "bob@open.org", "secret-42")
+        };
+
+        let actual = validate_new_user(req).unwrap();
+        assert_eq!(actual.display_name.as_deref(), Some("Bob Smith"));
+        assert_eq!(actual.bio, None);
+    }
+
+    #[test]
+    fn validate_update_checks_profile_fields() {
+        let req = UpdateRequestBody {
+            bio: Some("b".repeat(501)),
+            website: Some("ftp://open.org".to_string()),
+            avatar_url: Some(" ".to_string()),
+            ..Default::default()
+        };
+
+        let err = validate_update(req).unwrap_err();
+        assert_eq!(fields(err), vec!["bio", "website"]);
+    }
+
+    #[test]
+    fn urls_must_be_http_or_https() {
+        assert!(is_valid_url("https://open.org/bob.png"));
+        assert!(is_valid_url("http://open.org"));
+        assert!(!is_valid_url("https://"));
+        assert!(!is_valid_url("javascript:alert(1)"));
+        assert!(!is_valid_url("https://open.org/bob smith"));
+    }
+}
@@ -1,45 +1,152 @@
-use std::collections::HashMap;
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+use uuid::Uuid;
 
-use serde_json::{json, Value};
+use crate::follow::model::FollowCounts;
+use crate::pagination::Cursor;
 
-use super::model::User;
+use super::model::{User, Visibility};
 
-pub fn user_list(users: &[User]) -> Value {
-    let users_json: Vec<HashMap<String, String>> = users
-        .iter()
-        .map(|user| {
-            let mut resp = HashMap::new();
-            resp.insert("id".to_string(), user.id.to_string());
-            resp.insert("username".to_string(), user.username.clone());
-            resp.insert("email".to_string(), user.email.clone());
-            resp
-        })
-        .collect();
+/// A user as listed in `GET /users`; `email` only appears for the owner and
+/// admins.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct UserSummary<'a> {
+    pub id: Uuid,
+    pub username: &'a str,
+    pub display_name: Option<&'a str>,
+    pub avatar_url: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<&'a str>,
+}
+
+/// A full profile. Fields the viewer may not see are left out of the JSON
+/// rather than nulled, so `location: null` still means "not set".
+#[derive(Serialize, PartialEq, Debug)]
+pub struct UserDetails<'a> {
+    pub id: Uuid,
+    pub username: &'a str,
+    pub display_name: Option<&'a str>,
+    pub bio: Option<&'a str>,
+    pub avatar_url: Option<&'a str>,
+    pub website: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Option<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_admin: Option<bool>,
+    pub created_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<NaiveDateTime>,
+    pub followers_count: i64,
+    pub following_count: i64,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct UserPage<'a> {
+    pub data: Vec<UserSummary<'a>>,
+    pub next_cursor: Option<Cursor>,
+}
 
-    serde_json::to_value(users_json).unwrap()
+#[derive(Serialize, PartialEq, Debug)]
+pub struct UserCreated {
+    pub id: Uuid,
 }
 
-pub fn user_details(user: &User) -> Value {
-    json!({
-        "id": user.id,
-        "username": user.username,
-        "password": "*****",
-        "email": user.email
-    })
+#[derive(Serialize, PartialEq, Debug)]
+pub struct UserDeletion {
+    pub id: Uuid,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub purge_after: Option<NaiveDateTime>,
 }
 
-pub fn user_create(user: &User) -> Value {
-    json!({ "id": user.id })
+#[derive(Serialize, PartialEq, Debug)]
+pub struct UserDeactivation {
+    pub id: Uuid,
+    pub deactivated_at: Option<NaiveDateTime>,
+}
+
+pub fn user_summary(user: &User, visibility: Visibility) -> UserSummary<'_> {
+    UserSummary {
+        id: user.id,
+        username: &user.username,
+        display_name: user.display_name.as_deref(),
+        avatar_url: user.avatar_url.as_deref(),
+        email: Some(user.email.as_str()).filter(|_| visibility.shows_private()),
+    }
+}
+
+/// Listings don't look up follow edges, so followers get the public view.
+pub fn user_page<'a>(
+    users: &'a [User],
+    next_cursor: Option<Cursor>,
+    viewer: Option<&User>,
+) -> UserPage<'a> {
+    UserPage {
+        data: users
+            .iter()
+            .map(|user| user_summary(user, Visibility::of(viewer, user, false)))
+            .collect(),
+        next_cursor,
+    }
+}
+
+pub fn user_details<'a>(
+    user: &'a User,
+    counts: &FollowCounts,
+    visibility: Visibility,
+) -> UserDetails<'a> {
+    let private = visibility.shows_private();
+    UserDetails {
+        id: user.id,
+        username: &user.username,
+        display_name: user.display_name.as_deref(),
+        bio: user.bio.as_deref(),
+        avatar_url: user.avatar_url.as_deref(),
+        website: user.website.as_deref(),
+        location: Some(user.location.as_deref())
+            .filter(|_| visibility.shows_location()),
+        email: Some(user.email.as_str()).filter(|_| private),
+        email_verified: Some(user.email_verified_at.is_some())
+            .filter(|_| private),
+        is_admin: Some(user.is_admin).filter(|_| private),
+        created_at: user.created_at,
+        updated_at: Some(user.updated_at).filter(|_| private),
+        followers_count: counts.followers,
+        following_count: counts.following,
+    }
+}
+
+pub fn user_create(user: &User) -> UserCreated {
+    UserCreated { id: user.id }
+}
+
+/// `purge_after` tells the owner how long they have to change their mind.
+pub fn user_deletion(user: &User, grace: Duration) -> UserDeletion {
+    UserDeletion {
+        id: user.id,
+        deleted_at: user.deleted_at,
+        purge_after: user.deleted_at.map(|deleted_at| deleted_at + grace),
+    }
+}
+
+pub fn user_deactivation(user: &User) -> UserDeactivation {
+    UserDeactivation {
+        id: user.id,
+        deactivated_at: user.deactivated_at,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::Utc;
     use fake::faker::internet::en::FreeEmail;
     use fake::faker::internet::en::Password;
     use fake::faker::name::en::Name;
     use fake::Fake;
-    use serde_json::json;
-    use uuid::Uuid;
+    use serde_json::{json, to_value, Value};
 
     use super::*;
 
@@ -49,35 +156,62 @@ mod tests {
             username: Name().fake(),
             password: Password(5..10).fake(),
             email: FreeEmail().fake(),
+            display_name: Some("Bob".to_string()),
+            bio: None,
+            avatar_url: None,
+            location: Some("Lisbon".to_string()),
+            website: Some("https://open.org".to_string()),
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            is_admin: false,
+            deactivated_at: None,
+            deleted_at: None,
+            email_verified_at: None,
+            password_hashed_at: None,
+        }
+    }
+
+    fn counts() -> FollowCounts {
+        FollowCounts {
+            followers: 3,
+            following: 1,
         }
     }
 
     #[test]
-    fn user_list_view_returns_id_username_and_email() {
+    fn user_page_shows_email_only_to_its_owner() {
         let bob = create_fake_users();
         let alice = create_fake_users();
         let users = vec![bob.clone(), alice.clone()];
 
-        let actual = user_list(&users);
-        let expected = json!([
-            {
-                "id": users[0].id,
-                "username": users[0].username,
-                "email": users[0].email
+        let cursor = Cursor::new(alice.created_at, alice.id);
+        let actual =
+            to_value(user_page(&users, Some(cursor), Some(&bob))).unwrap();
+        let expected = json!({
+            "data": [{
+                "id": bob.id,
+                "username": bob.username,
+                "display_name": "Bob",
+                "avatar_url": null,
+                "email": bob.email
             }, {
-                "id": users[1].id,
-                "username": users[1].username,
-                "email": users[1].email
-            }
-        ]);
+                "id": alice.id,
+                "username": alice.username,
+                "display_name": "Bob",
+                "avatar_url": null
+            }],
+            "next_cursor": cursor.to_string()
+        });
 
         assert_eq!(actual, expected);
+        let empty = to_value(user_page(&[], None, None)).unwrap();
+        assert_eq!(empty["next_cursor"], Value::Null);
     }
 
     #[test]
     fn user_create_view_returns_id() {
         let bob = create_fake_users();
-        let actual = user_create(&bob);
+        let actual = to_value(user_create(&bob)).unwrap();
 
         let expected = json!({ "id": bob.id });
 
@@ -85,16 +219,42 @@ mod tests {
     }
 
     #[test]
-    fn user_details_view_returns_user_details() {
+    fn user_details_public_view_hides_private_fields() {
         let bob = create_fake_users();
         let expected = json!({
             "id": bob.id,
             "username": bob.username,
-            "password": "*****",
-            "email": bob.email
+            "display_name": "Bob",
+            "bio": null,
+            "avatar_url": null,
+            "website": "https://open.org",
+            "created_at": bob.created_at,
+            "followers_count": 3,
+            "following_count": 1
         });
 
-        let actual = user_details(&bob);
+        let actual =
+            to_value(user_details(&bob, &counts(), Visibility::Public))
+                .unwrap();
         assert_eq!(expected, actual)
     }
+
+    #[test]
+    fn user_details_widens_with_visibility() {
+        let bob = create_fake_users();
+
+        let follower =
+            to_value(user_details(&bob, &counts(), Visibility::Follower))
+                .unwrap();
+        assert_eq!(follower["location"], "Lisbon");
+        assert!(follower.get("email").is_none());
+
+        let owner =
+            to_value(user_details(&bob, &counts(), Visibility::Owner)).unwrap();
+        assert_eq!(owner["email"], json!(bob.email));
+        assert_eq!(owner["email_verified"], false);
+        assert_eq!(owner["is_admin"], false);
+        assert_eq!(owner["updated_at"], json!(bob.updated_at));
+        assert!(owner.get("password").is_none());
+    }
 }
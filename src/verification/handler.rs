@@ -0,0 +1,219 @@
+use std::convert::Infallible;
+
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{PgConnection, QueryResult};
+use warp::http::StatusCode;
+use warp::reply::{json, with_status, Json, WithStatus};
+use warp::{path, post, Filter};
+
+use crate::config::{AccountSettings, SessionSettings};
+use crate::db::{blocking, with_db_conn};
+use crate::error::ApiError;
+use crate::filters::{with_account, with_settings};
+use crate::mailer::{with_mailer, Email, SharedMailer};
+use crate::session::handler::with_auth;
+use crate::session::token;
+use crate::user::model::User;
+use crate::ConnectionPool;
+
+use super::model::NewEmailVerification;
+use super::repository::VerificationRepo;
+use super::view;
+
+pub fn routes(
+    pool: ConnectionPool,
+    settings: SessionSettings,
+    account: AccountSettings,
+    mailer: SharedMailer,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let verification_request_route = path!("email-verifications")
+        .and(post())
+        .and(with_auth(pool.clone(), settings.clone()))
+        .and(with_db_conn(pool.clone()))
+        .and(with_settings(settings.clone()))
+        .and(with_account(account))
+        .and(with_mailer(mailer))
+        .and_then(verification_request);
+
+    let verification_confirm_route = path!("email-verifications" / String)
+        .and(post())
+        .and(with_db_conn(pool))
+        .and(with_settings(settings))
+        .and_then(verification_confirm);
+
+    verification_request_route.or(verification_confirm_route)
+}
+
+/// Stores a fresh link for `user` to confirm their current address and
+/// returns the email carrying it. Callers send it once the connection is
+/// back in the pool, so a slow mail server can't starve it.
+pub fn issue_verification(
+    conn: &PgConnection,
+    user: &User,
+    settings: &SessionSettings,
+    account: &AccountSettings,
+) -> QueryResult<Email> {
+    let token = token::generate();
+    VerificationRepo::create(
+        conn,
+        NewEmailVerification::new(
+            user,
+            &token,
+            &settings.secret,
+            account.verification_ttl,
+        ),
+    )?;
+    let link = format!("{}/verify-email?token={}", account.app_url, token);
+    Ok(view::verification_email(user, &link))
+}
+
+async fn verification_request(
+    current_user: User,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    settings: SessionSettings,
+    account: AccountSettings,
+    mailer: SharedMailer,
+) -> Result<WithStatus<Json>, Infallible> {
+    if current_user.email_verified_at.is_some() {
+        return Ok(ApiError::BadRequest(
+            "Email is already verified".to_string(),
+        )
+        .reply());
+    }
+
+    let issued = blocking(move || {
+        issue_verification(&conn, &current_user, &settings, &account)
+            .map(|email| (current_user, email))
+    })
+    .await;
+    let result = match issued {
+        Ok((user, email)) => {
+            blocking(move || mailer.send(&email).map(|_| user)).await
+        }
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(user) => {
+            let resp = view::verification_request(&user);
+            Ok(with_status(json(&resp), StatusCode::ACCEPTED))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+async fn verification_confirm(
+    token: String,
+    conn: PooledConnection<ConnectionManager<PgConnection>>,
+    settings: SessionSettings,
+) -> Result<WithStatus<Json>, Infallible> {
+    let token_hash = token::digest(&settings.secret, token.trim());
+    match blocking(move || VerificationRepo::confirm(&conn, &token_hash)).await
+    {
+        Ok(user) => {
+            let resp = view::verification_confirm(&user);
+            Ok(with_status(json(&resp), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+    use warp::test::request;
+
+    use crate::error::handle_rejection;
+    use crate::mailer::memory::MemoryMailer;
+    use crate::session::model::NewSession;
+    use crate::session::repository::SessionRepo;
+    use crate::test_helpers::{
+        account_settings, create_user, establish_connection, session_settings,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn emailed_token_verifies_address() {
+        let pool = establish_connection();
+        let conn = pool.get().unwrap();
+        let bob = create_user(&conn);
+        SessionRepo::create(
+            &conn,
+            NewSession::new(bob.id, "token", &session_settings()),
+        )
+        .unwrap();
+        drop(conn);
+        let mailer = Arc::new(MemoryMailer::default());
+        let filter = routes(
+            pool,
+            session_settings(),
+            account_settings(),
+            mailer.clone(),
+        )
+        .recover(handle_rejection);
+
+        let resp = request()
+            .method("POST")
+            .path("/email-verifications")
+            .header("authorization", "Bearer token")
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, bob.email);
+        let prefix = "https://social.test/verify-email?token=";
+        let token: String = sent[0].body[sent[0].body.find(prefix).unwrap()..]
+            [prefix.len()..]
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .collect();
+
+        let resp = request()
+            .method("POST")
+            .path(&format!("/email-verifications/{}", token))
+            .reply(&filter)
+            .await;
+        let body: Value = serde_json::from_slice(resp.body()).unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body["id"], json!(bob.id));
+        assert!(!body["email_verified_at"].is_null());
+
+        let resp = request()
+            .method("POST")
+            .path(&format!("/email-verifications/{}", token))
+            .reply(&filter)
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn verification_request_rejects_verified_users() {
+        let pool = establish_connection();
+        let bob = User {
+            email_verified_at: Some(chrono::Utc::now().naive_utc()),
+            ..create_user(&pool.get().unwrap())
+        };
+        let mailer = Arc::new(MemoryMailer::default());
+
+        let resp = verification_request(
+            bob,
+            pool.get().unwrap(),
+            session_settings(),
+            account_settings(),
+            mailer.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            warp::Reply::into_response(resp).status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(mailer.sent().is_empty());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod handler;
+pub mod model;
+pub mod repository;
+mod view;
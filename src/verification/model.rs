@@ -0,0 +1,39 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{Insertable, Queryable};
+use uuid::Uuid;
+
+use crate::schema::email_verifications;
+use crate::session::token;
+use crate::user::model::User;
+
+#[derive(Queryable, PartialEq, Clone, Debug)]
+pub struct EmailVerification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub email: String,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "email_verifications"]
+pub struct NewEmailVerification {
+    pub user_id: Uuid,
+    pub email: String,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+impl NewEmailVerification {
+    /// The token is tied to the address it was sent to, so it stops working
+    /// once the user changes their email.
+    pub fn new(user: &User, token: &str, secret: &str, ttl: Duration) -> Self {
+        NewEmailVerification {
+            user_id: user.id,
+            email: user.email.clone(),
+            token_hash: token::digest(secret, token),
+            expires_at: Utc::now().naive_utc() + ttl,
+        }
+    }
+}
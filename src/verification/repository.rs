@@ -0,0 +1,118 @@
+use chrono::Utc;
+use diesel::dsl::now;
+use diesel::prelude::*;
+use diesel::QueryResult;
+
+use crate::schema::{email_verifications, users};
+use crate::user::model::User;
+
+use super::model::{EmailVerification, NewEmailVerification};
+
+pub struct VerificationRepo;
+
+impl VerificationRepo {
+    pub fn create(
+        conn: &PgConnection,
+        new_verification: NewEmailVerification,
+    ) -> QueryResult<EmailVerification> {
+        diesel::insert_into(email_verifications::table)
+            .values(new_verification)
+            .get_result(conn)
+    }
+
+    /// Marks the email behind a live token as verified and discards every
+    /// outstanding token for that user. Expired tokens, and tokens for an
+    /// address the user has since replaced, are `NotFound`.
+    pub fn confirm(conn: &PgConnection, token_hash: &str) -> QueryResult<User> {
+        conn.transaction(|| {
+            let verification: EmailVerification = email_verifications::table
+                .filter(email_verifications::token_hash.eq(token_hash))
+                .filter(
+                    email_verifications::expires_at.gt(Utc::now().naive_utc()),
+                )
+                .first(conn)?;
+
+            let user: User = diesel::update(
+                users::table
+                    .find(verification.user_id)
+                    .filter(users::email.eq(&verification.email)),
+            )
+            .set(users::email_verified_at.eq(now.nullable()))
+            .get_result(conn)?;
+
+            diesel::delete(
+                email_verifications::table
+                    .filter(email_verifications::user_id.eq(user.id)),
+            )
+            .execute(conn)?;
+            Ok(user)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::session::token;
+    use crate::test_helpers::{create_user, establish_connection};
+    use crate::user::model::UpdateUser;
+    use crate::user::repository::UserRepo;
+
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    fn issue(conn: &PgConnection, user: &User, token: &str, ttl: Duration) {
+        VerificationRepo::create(
+            conn,
+            NewEmailVerification::new(user, token, SECRET, ttl),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn confirm_verifies_email_and_consumes_tokens() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        issue(&conn, &bob, "first", Duration::hours(1));
+        issue(&conn, &bob, "second", Duration::hours(1));
+
+        let user =
+            VerificationRepo::confirm(&conn, &token::digest(SECRET, "second"))
+                .unwrap();
+        assert_eq!(user.id, bob.id);
+        assert!(user.email_verified_at.is_some());
+
+        let reused =
+            VerificationRepo::confirm(&conn, &token::digest(SECRET, "first"));
+        assert_eq!(reused, Err(diesel::result::Error::NotFound));
+    }
+
+    #[test]
+    fn confirm_rejects_expired_tokens() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        issue(&conn, &bob, "token", Duration::minutes(-1));
+
+        let result =
+            VerificationRepo::confirm(&conn, &token::digest(SECRET, "token"));
+        assert_eq!(result, Err(diesel::result::Error::NotFound));
+    }
+
+    #[test]
+    fn confirm_rejects_tokens_for_a_replaced_address() {
+        let conn = establish_connection().get().unwrap();
+        let bob = create_user(&conn);
+        issue(&conn, &bob, "token", Duration::hours(1));
+        let changes = UpdateUser {
+            email: Some("bob@open.org".to_string()),
+            ..Default::default()
+        };
+        UserRepo::update(&conn, bob.id, changes).unwrap();
+
+        let result =
+            VerificationRepo::confirm(&conn, &token::digest(SECRET, "token"));
+        assert_eq!(result, Err(diesel::result::Error::NotFound));
+    }
+}
@@ -0,0 +1,29 @@
+use serde_json::{json, Value};
+
+use crate::mailer::Email;
+use crate::user::model::User;
+
+pub fn verification_email(user: &User, link: &str) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Confirm your email address".to_string(),
+        body: format!(
+            "Hi {},\n\nConfirm this is your email address by opening the \
+             link below:\n\n{}\n\nIf you didn't sign up, you can ignore \
+             this message.",
+            user.username, link
+        ),
+    }
+}
+
+pub fn verification_request(user: &User) -> Value {
+    json!({ "email": user.email })
+}
+
+pub fn verification_confirm(user: &User) -> Value {
+    json!({
+        "id": user.id,
+        "email": user.email,
+        "email_verified_at": user.email_verified_at
+    })
+}